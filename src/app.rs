@@ -1,5 +1,6 @@
+use quickget_core::data_structures::Arch;
 use ratatui::{
-    crossterm::event::KeyEvent,
+    crossterm::event::{KeyEvent, MouseButton, MouseEvent, MouseEventKind},
     layout::{Constraint, Direction, Layout, Rect},
     style::{Style, Stylize},
     text::{Line, Span},
@@ -9,25 +10,47 @@ use ratatui::{
 
 use crate::{
     arch_selection::ArchSelection,
+    basket::BasketPage,
     complete::CompletePage,
     download::DownloadPage,
     download_options::DownloadOptions,
     edition_selection::EditionSelection,
     error_display::ErrorDisplay,
+    help::Help,
     keybinds::{FinishedKeybinds, KeyBind},
+    keymap::{keymap, Command},
+    library::LibraryPage,
     os_selection::OSSelection,
     release_selection::ReleaseSelection,
     url_list::UrlList,
 };
 
+const BREADCRUMB_SEPARATOR: &str = " → ";
+
 pub struct App {
+    arch: Option<Arch>,
     page_stack: Vec<Page>,
+    help: Option<Help>,
 }
 
 impl App {
-    pub fn new() -> Self {
+    pub fn new(arch: Option<Arch>) -> Self {
         Self {
-            page_stack: vec![Page::ArchSelection(ArchSelection::new())],
+            arch,
+            page_stack: initial_pages(arch),
+            help: None,
+        }
+    }
+
+    /// Return to the first page, discarding all selections and searches
+    pub fn start_over(&mut self) {
+        self.page_stack = initial_pages(self.arch);
+    }
+
+    /// Return to an earlier page, keeping its state. Depths start at 1, the first page
+    pub fn jump_to(&mut self, depth: usize) {
+        if depth > 0 {
+            self.page_stack.truncate(depth);
         }
     }
 
@@ -44,30 +67,75 @@ impl App {
     }
 
     pub fn title(&self) -> Line<'static> {
-        let page_names: Vec<&str> = self.page_stack.iter().map(|p| p.page_name()).collect();
-        let (last, rest) = page_names.split_last().unwrap();
+        self.title_spans()
+            .into_iter()
+            .map(|(span, _)| span)
+            .collect()
+    }
 
-        let title = Span::styled("QuickOSDL", Style::default().bold());
-        let prev_navigation = Span::raw(format!(
-            ": Start -> {}",
-            rest.iter().flat_map(|n| [n, " -> "]).collect::<String>()
-        ));
-        let curr_page = Span::styled(*last, Style::default().bold());
+    /// A breadcrumb for each page, describing the selection it was opened with.
+    /// Parts of the selection already shown by the previous breadcrumb are left out
+    fn breadcrumbs(&self) -> Vec<String> {
+        let selections: Vec<Vec<String>> = self.page_stack.iter().map(Page::selection).collect();
+        let mut prev: &[String] = &[];
+        selections
+            .iter()
+            .map(|selection| {
+                let common = selection
+                    .iter()
+                    .zip(prev)
+                    .take_while(|(a, b)| a == b)
+                    .count()
+                    .min(selection.len().saturating_sub(1));
+                prev = selection;
+                selection[common..].join(" ")
+            })
+            .collect()
+    }
 
-        Line::from(vec![
-            Span::raw(" "),
-            title,
-            prev_navigation,
-            curr_page,
-            Span::raw(" "),
-        ])
+    /// Spans of the title, along with the depth of the page stack that each breadcrumb returns to
+    fn title_spans(&self) -> Vec<(Span<'static>, Option<usize>)> {
+        let mut spans = vec![
+            (Span::raw(" "), None),
+            (Span::styled("QuickOSDL", Style::default().bold()), None),
+            (Span::raw(": "), None),
+        ];
+        let breadcrumbs = self.breadcrumbs();
+        let last = breadcrumbs.len() - 1;
+        for (i, breadcrumb) in breadcrumbs.into_iter().enumerate() {
+            if i > 0 {
+                spans.push((Span::raw(BREADCRUMB_SEPARATOR), None));
+            }
+            if i == last {
+                spans.push((Span::styled(breadcrumb, Style::default().bold()), None));
+            } else {
+                spans.push((Span::raw(breadcrumb), Some(i + 1)));
+            }
+        }
+        spans.push((Span::raw(" "), None));
+        spans
+    }
+
+    /// The depth of the breadcrumb drawn at a column of the title
+    fn breadcrumb_at(&self, column: u16) -> Option<usize> {
+        // The title is drawn on the top border, after the corner
+        let mut start = 1;
+        self.title_spans().into_iter().find_map(|(span, depth)| {
+            let end = start + span.width() as u16;
+            let hit = (start..end).contains(&column);
+            start = end;
+            depth.filter(|_| hit)
+        })
     }
 
     pub fn draw(&mut self, frame: &mut Frame) {
         let block = Block::bordered().title(self.title());
         let inner_area = block.inner(frame.area());
 
-        let keybinds = self.current_page().keybinds();
+        let mut keybinds = self.current_page().keybinds();
+        if self.current_page().allows_navigation() {
+            keybinds.extend(self.global_keybinds());
+        }
         let keybinds = FinishedKeybinds::new(keybinds, inner_area.width);
         let length = keybinds.length();
         let chunks = Layout::default()
@@ -82,20 +150,125 @@ impl App {
 
         self.current_page().draw(frame, chunks[0]);
         frame.render_widget(block, frame.area());
+        if let Some(help) = &mut self.help {
+            help.draw(frame, frame.area());
+        }
+    }
+
+    /// Keybinds handled by the app rather than the current page
+    fn global_keybinds(&self) -> Vec<KeyBind> {
+        let keymap = keymap();
+        let mut binds = vec![
+            keymap.keybind(Command::Help),
+            keymap.keybind(Command::StartOver),
+        ];
+        if self.page_stack.len() > 2 {
+            binds.push(KeyBind::new(
+                [key_range(keymap.keys(Command::JumpToPage))],
+                Command::JumpToPage.description(),
+            ));
+        }
+        binds
+    }
+
+    fn open_help(&mut self) {
+        let name = self.breadcrumbs().pop().unwrap_or_default();
+        let page = self.page_stack.last().unwrap();
+        self.help = Some(Help::new(
+            name,
+            page.description(),
+            page.keybinds(),
+            self.global_keybinds(),
+        ));
     }
 
     pub fn handle_key(&mut self, key: &KeyEvent) -> bool {
-        if let Some(action) = self.current_page().handle_key(key) {
-            match action {
-                Action::Exit => return true,
-                Action::PrevPage => self.pop_page(),
-                Action::NextPage(page) => self.push_page(page),
+        if let Some(help) = &mut self.help {
+            if !help.handle_key(key) {
+                self.help = None;
+            }
+            return false;
+        }
+        if self.current_page().allows_navigation() {
+            if keymap().command(key) == Some(Command::Help) {
+                self.open_help();
+                return false;
+            }
+            if keymap().command(key) == Some(Command::StartOver) {
+                self.start_over();
+                return false;
+            }
+            if let Some(depth) = keymap().key_number(Command::JumpToPage, key) {
+                self.jump_to(depth);
+                return false;
             }
         }
+        let action = self.current_page().handle_key(key);
+        self.apply(action)
+    }
+
+    pub fn handle_mouse(&mut self, mouse: &MouseEvent) -> bool {
+        if let Some(help) = &mut self.help {
+            if !help.handle_mouse(mouse) {
+                self.help = None;
+            }
+            return false;
+        }
+        if mouse.row == 0 {
+            if let (MouseEventKind::Down(MouseButton::Left), Some(depth)) =
+                (mouse.kind, self.breadcrumb_at(mouse.column))
+            {
+                if self.current_page().allows_navigation() {
+                    self.jump_to(depth);
+                }
+            }
+            return false;
+        }
+        let action = self.current_page().handle_mouse(mouse);
+        self.apply(action)
+    }
+
+    /// Let the current page pick up changes from background work
+    pub fn update(&mut self) -> bool {
+        let action = self.current_page().update();
+        self.apply(action)
+    }
+
+    /// Carry out a page's action, returning whether the app should exit
+    fn apply(&mut self, action: Option<Action>) -> bool {
+        match action {
+            Some(Action::Exit) => return true,
+            Some(Action::PrevPage) => self.pop_page(),
+            Some(Action::NextPage(page)) => self.push_page(page),
+            None => {}
+        }
         false
     }
 }
 
+/// Keys numbered by their position, shown as a range such as `Alt+1-9`
+fn key_range(mut keys: impl Iterator<Item = String>) -> String {
+    let first = keys.next().unwrap_or_default();
+    let Some(last) = keys.last() else {
+        return first;
+    };
+    let common = first
+        .char_indices()
+        .zip(last.chars())
+        .find(|((_, a), b)| a != b)
+        .map_or(first.len(), |((i, _), _)| i);
+    format!("{first}-{}", &last[common..])
+}
+
+/// Start on the OS list if an architecture is known, keeping the architecture page below it
+fn initial_pages(arch: Option<Arch>) -> Vec<Page> {
+    let mut pages = vec![Page::ArchSelection(ArchSelection::new(arch))];
+    if let Some(arch) = arch {
+        pages.push(Page::OSSelection(OSSelection::new(arch)));
+    }
+    pages
+}
+
 pub enum Action {
     Exit,
     PrevPage,
@@ -109,6 +282,8 @@ pub enum Page {
     EditionSelection(EditionSelection),
     DownloadOptions(DownloadOptions),
     Download(DownloadPage),
+    Basket(BasketPage),
+    Library(LibraryPage),
     UrlList(UrlList),
     Complete(CompletePage),
     Error(ErrorDisplay),
@@ -123,6 +298,8 @@ impl Page {
             Page::EditionSelection(edition_selection) => edition_selection.draw(frame, area),
             Page::DownloadOptions(download_options) => download_options.draw(frame, area),
             Page::Download(download_page) => download_page.draw(frame, area),
+            Page::Basket(basket_page) => basket_page.draw(frame, area),
+            Page::Library(library_page) => library_page.draw(frame, area),
             Page::UrlList(url_list) => url_list.draw(frame, area),
             Page::Complete(complete_page) => complete_page.draw(frame, area),
             Page::Error(error_display) => error_display.draw(frame, area),
@@ -136,23 +313,109 @@ impl Page {
             Page::EditionSelection(edition_selection) => edition_selection.handle_key(key),
             Page::DownloadOptions(download_options) => download_options.handle_key(key),
             Page::Download(download_page) => download_page.handle_key(key),
+            Page::Basket(basket_page) => basket_page.handle_key(key),
+            Page::Library(library_page) => library_page.handle_key(key),
             Page::UrlList(url_list) => url_list.handle_key(key),
             Page::Complete(complete_page) => complete_page.handle_key(key),
             Page::Error(error_display) => error_display.handle_key(key),
         }
     }
 
-    fn page_name(&self) -> &'static str {
+    fn update(&mut self) -> Option<Action> {
+        match self {
+            Page::OSSelection(os_selection) => os_selection.update(),
+            Page::Download(download_page) => download_page.update(),
+            Page::Library(library_page) => library_page.update(),
+            _ => None,
+        }
+    }
+
+    fn handle_mouse(&mut self, mouse: &MouseEvent) -> Option<Action> {
+        match self {
+            Page::ArchSelection(arch_selection) => arch_selection.handle_mouse(mouse),
+            Page::OSSelection(os_selection) => os_selection.handle_mouse(mouse),
+            Page::ReleaseSelection(release_selection) => release_selection.handle_mouse(mouse),
+            Page::EditionSelection(edition_selection) => edition_selection.handle_mouse(mouse),
+            Page::DownloadOptions(download_options) => download_options.handle_mouse(mouse),
+            Page::Download(download_page) => download_page.handle_mouse(mouse),
+            Page::Basket(basket_page) => basket_page.handle_mouse(mouse),
+            Page::Library(library_page) => library_page.handle_mouse(mouse),
+            Page::UrlList(url_list) => url_list.handle_mouse(mouse),
+            Page::Complete(_) | Page::Error(_) => None,
+        }
+    }
+
+    /// The selection which the page was opened with, from the most general part to the most specific.
+    /// Pages which don't represent a selection use their name
+    fn selection(&self) -> Vec<String> {
+        match self {
+            Page::ArchSelection(_) => vec!["Arch".into()],
+            Page::OSSelection(os_selection) => vec![os_selection.arch().to_string()],
+            Page::ReleaseSelection(release_selection) => release_selection.selection(),
+            Page::EditionSelection(edition_selection) => edition_selection.selection(),
+            Page::DownloadOptions(download_options) => download_options.selection(),
+            Page::Download(_) => vec!["Download".into()],
+            Page::Basket(_) => vec!["Basket".into()],
+            Page::Library(_) => vec!["Library".into()],
+            Page::UrlList(_) => vec!["URLs".into()],
+            Page::Complete(_) => vec!["Complete".into()],
+            Page::Error(_) => vec!["Error".into()],
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        match self {
+            Page::ArchSelection(_) => {
+                "Choose the CPU architecture to list images for. Your choice is remembered for next time."
+            }
+            Page::OSSelection(_) => {
+                "Operating systems with images for the chosen architecture. Favourites and recent \
+                 selections are pinned to the top, and searching matches names and descriptions."
+            }
+            Page::ReleaseSelection(_) => {
+                "Releases of the chosen OS, with long term support releases grouped together. \
+                 Adding a release to the basket adds all of its editions."
+            }
+            Page::EditionSelection(_) => {
+                "Editions of the chosen release. Add several to the basket to download them together."
+            }
+            Page::DownloadOptions(_) => {
+                "Download the image now, or list the URLs of its files to download them another way."
+            }
+            Page::Download(_) => {
+                "Progress of each file being downloaded. Checksums are verified as data arrives. \
+                 Click the controls beside a download to pause, resume or cancel it."
+            }
+            Page::Basket(_) => {
+                "Images marked for download, which can span several OSes and architectures, \
+                 along with their total size. Download them all at once from here."
+            }
+            Page::Library(_) => {
+                "Images downloaded before, and whether they're unchanged, modified, missing or superseded by a \
+                 newer release. Re-verify, delete or download the latest release of an image from here."
+            }
+            Page::UrlList(_) => {
+                "The files which make up the image, with where each will be saved, its size, URL and checksum."
+            }
+            Page::Complete(_) => "Every download finished, and checksums matched where available.",
+            Page::Error(_) => "Something went wrong. The errors are listed on the page.",
+        }
+    }
+
+    /// Whether app-wide navigation keys can be used. They're disabled while typing a search
+    /// or a vim-style key sequence, and while downloading, since leaving the download page cancels the downloads
+    fn allows_navigation(&self) -> bool {
         match self {
-            Page::ArchSelection(_) => "Arch",
-            Page::OSSelection(_) => "OS",
-            Page::ReleaseSelection(_) => "Release",
-            Page::EditionSelection(_) => "Edition",
-            Page::DownloadOptions(_) => "Download Options",
-            Page::Download(_) => "Download",
-            Page::UrlList(_) => "URLs",
-            Page::Complete(_) => "Complete",
-            Page::Error(_) => "Error",
+            Page::ArchSelection(arch_selection) => arch_selection.accepts_commands(),
+            Page::OSSelection(os_selection) => os_selection.accepts_commands(),
+            Page::ReleaseSelection(release_selection) => release_selection.accepts_commands(),
+            Page::EditionSelection(edition_selection) => edition_selection.accepts_commands(),
+            Page::DownloadOptions(download_options) => download_options.accepts_commands(),
+            Page::Basket(basket_page) => basket_page.accepts_commands(),
+            Page::Library(library_page) => library_page.accepts_commands(),
+            Page::UrlList(url_list) => url_list.accepts_commands(),
+            Page::Download(download_page) => download_page.is_finished(),
+            Page::Complete(_) | Page::Error(_) => true,
         }
     }
 
@@ -164,6 +427,8 @@ impl Page {
             Page::EditionSelection(edition_selection) => edition_selection.keybinds(),
             Page::DownloadOptions(download_options) => download_options.keybinds(),
             Page::Download(download_page) => download_page.keybinds(),
+            Page::Basket(basket_page) => basket_page.keybinds(),
+            Page::Library(library_page) => library_page.keybinds(),
             Page::UrlList(url_list) => url_list.keybinds(),
             Page::Complete(complete_page) => complete_page.keybinds(),
             Page::Error(error_display) => error_display.keybinds(),
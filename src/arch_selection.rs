@@ -1,9 +1,11 @@
 use std::borrow::Cow;
 
+use itertools::Itertools;
 use quickget_core::data_structures::Arch;
 use ratatui::{
-    crossterm::event::{KeyCode, KeyEvent},
+    crossterm::event::{KeyEvent, MouseEvent},
     layout::Rect,
+    text::{Line, Span},
     widgets::ListItem,
     Frame,
 };
@@ -11,16 +13,24 @@ use ratatui::{
 use crate::{
     app::{Action, Page},
     keybinds::KeyBind,
+    keymap::{keymap, Command},
     os_selection::OSSelection,
-    searchable_list::{SearchableItem, SearchableList},
+    searchable_list::{Highlights, SearchableItem, SearchableList},
+    state::{update_state, with_state},
 };
 
 impl SearchableItem for Arch {
-    fn to_list_item(&self, _: usize) -> ListItem<'_> {
-        ListItem::new(self.to_string())
+    fn to_list_item(&self, _: usize, highlights: Highlights) -> ListItem<'_> {
+        let name = self.to_string();
+        let line: Line = highlights
+            .spans(0, &name, 0)
+            .into_iter()
+            .map(|s| Span::styled(s.content.into_owned(), s.style))
+            .collect();
+        ListItem::new(line)
     }
-    fn to_filter(&self) -> Cow<'static, str> {
-        Cow::Owned(self.to_string())
+    fn filter_fields(&self) -> Vec<Cow<'_, str>> {
+        vec![Cow::Owned(self.to_string())]
     }
 }
 
@@ -28,23 +38,53 @@ pub struct ArchSelection {
     list: SearchableList<Arch>,
 }
 
+/// Parse an architecture from its name, as displayed in the architecture list
+pub fn parse_arch(name: &str) -> Result<Arch, String> {
+    Arch::iter().find(|a| a.to_string() == name).ok_or_else(|| {
+        let valid = Arch::iter().map(|a| a.to_string()).join(", ");
+        format!("unknown architecture '{name}', expected one of: {valid}")
+    })
+}
+
+/// The architecture of the running system, if images are available for it
+pub fn host_arch() -> Option<Arch> {
+    parse_arch(std::env::consts::ARCH).ok()
+}
+
 impl ArchSelection {
-    pub fn new() -> Self {
+    /// Create the page with the given architecture highlighted, or the last used one otherwise
+    pub fn new(selected: Option<Arch>) -> Self {
         let architectures: Vec<Arch> = Arch::iter().collect();
-        Self {
-            list: SearchableList::new(architectures),
+        let selected = selected
+            .map(|a| a.to_string())
+            .or_else(|| with_state(|state| state.last_arch.clone()));
+        let mut list = SearchableList::new(architectures.clone());
+        if let Some(index) = architectures
+            .iter()
+            .position(|a| Some(a.to_string()) == selected)
+        {
+            list.select(index);
         }
+        Self { list }
     }
     pub fn handle_key(&mut self, key: &KeyEvent) -> Option<Action> {
-        match key.code {
-            KeyCode::Char('q') if !self.list.is_searching() => Some(Action::Exit),
-            _ => self
-                .list
-                .handle_key(key)
-                .map(|arch| Action::NextPage(Page::OSSelection(OSSelection::new(*arch)))),
+        match keymap()
+            .command(key)
+            .filter(|_| self.list.accepts_commands())
+        {
+            Some(Command::Quit) => Some(Action::Exit),
+            _ => self.list.handle_key(key).map(open_arch),
         }
     }
 
+    pub fn accepts_commands(&self) -> bool {
+        self.list.accepts_commands()
+    }
+
+    pub fn handle_mouse(&mut self, mouse: &MouseEvent) -> Option<Action> {
+        self.list.handle_mouse(mouse).map(open_arch)
+    }
+
     pub fn draw(&mut self, frame: &mut Frame, area: Rect) {
         self.list.draw(frame, area);
     }
@@ -53,3 +93,8 @@ impl ArchSelection {
         self.list.keybinds(false)
     }
 }
+
+fn open_arch(arch: &Arch) -> Action {
+    update_state(|state| state.last_arch = Some(arch.to_string()));
+    Action::NextPage(Page::OSSelection(OSSelection::new(*arch)))
+}
@@ -0,0 +1,398 @@
+use std::{future::Future, path::Path, pin::Pin, process::Stdio, sync::Arc, time::Duration};
+
+use reqwest::{header::CONTENT_DISPOSITION, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt},
+    process::Command,
+};
+
+use crate::{
+    downloader::{DownloadError, Sink},
+    filename,
+    network::{HttpClient, Network, NetworkError},
+};
+
+/// Attempts at sending each request, when failures may be temporary
+const MAX_ATTEMPTS: u32 = 3;
+/// Delay before retrying a request, multiplied by the number of attempts so far
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// A way of fetching files. Backends deliver files through a [`Sink`],
+/// which decides where they're saved and verifies them once they're complete
+pub trait Backend: Send + Sync {
+    fn fetch<'a>(
+        &'a self,
+        url: &'a str,
+        sink: &'a mut Sink<'_>,
+    ) -> BoxFuture<'a, Result<(), DownloadError>>;
+
+    /// Whether downloads can be paused and rate limited, which needs their data to be written through the sink
+    fn can_pause(&self) -> bool {
+        true
+    }
+}
+
+/// The backends which can be chosen in the configuration
+#[derive(Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BackendKind {
+    /// Download within quickosdl
+    #[default]
+    Builtin,
+    /// Run aria2c for each file
+    Aria2c,
+    /// Run curl for each file
+    Curl,
+}
+
+impl BackendKind {
+    /// The backend, connecting with the network settings. The builtin backend sends requests with the client,
+    /// which should be created from the same settings
+    pub fn backend(self, client: &HttpClient, network: &Network) -> Arc<dyn Backend> {
+        match self {
+            BackendKind::Builtin => Arc::new(Http::new(client.clone())),
+            BackendKind::Aria2c => Arc::new(Aria2c::new(network.clone())),
+            BackendKind::Curl => Arc::new(Curl::new(network.clone())),
+        }
+    }
+}
+
+/// The backend for a URL. Local files are copied directly if `local_files` allows them,
+/// and anything else uses the default backend
+pub fn for_url(
+    url: &str,
+    default: &Arc<dyn Backend>,
+    local_files: bool,
+) -> Result<Arc<dyn Backend>, DownloadError> {
+    if !url.starts_with("file://") {
+        Ok(default.clone())
+    } else if local_files {
+        Ok(Arc::new(LocalFile))
+    } else {
+        Err(DownloadError::LocalFile(url.to_string()))
+    }
+}
+
+/// Streams files over HTTP, retrying failures which may be temporary
+pub struct Http {
+    client: HttpClient,
+}
+
+impl Http {
+    pub fn new(client: HttpClient) -> Self {
+        Self { client }
+    }
+}
+
+impl Backend for Http {
+    fn fetch<'a>(
+        &'a self,
+        url: &'a str,
+        sink: &'a mut Sink<'_>,
+    ) -> BoxFuture<'a, Result<(), DownloadError>> {
+        Box::pin(async move {
+            let mut response = request(&self.client, url, sink).await?;
+            let file_name = response
+                .headers()
+                .get(CONTENT_DISPOSITION)
+                .and_then(|header| {
+                    filename::from_content_disposition(&String::from_utf8_lossy(header.as_bytes()))
+                })
+                .or_else(|| filename::from_url(response.url().as_str()));
+            if sink
+                .start(file_name.as_deref(), response.content_length())?
+                .is_none()
+            {
+                return Ok(());
+            }
+            while let Some(chunk) = response.chunk().await.map_err(http_error)? {
+                sink.write(&chunk).await?;
+            }
+            Ok(())
+        })
+    }
+}
+
+async fn request(
+    client: &HttpClient,
+    url: &str,
+    sink: &mut Sink<'_>,
+) -> Result<reqwest::Response, DownloadError> {
+    let mut attempt = 1;
+    loop {
+        let response = match client.get(url).await {
+            Ok(response) => response.error_for_status().map_err(NetworkError::Request),
+            Err(e) => Err(e),
+        };
+        match response {
+            Err(NetworkError::Request(e)) if attempt < MAX_ATTEMPTS && is_transient(&e) => {
+                attempt += 1;
+                sink.retry(attempt, &e);
+                tokio::time::sleep(RETRY_DELAY * (attempt - 1)).await;
+            }
+            Err(NetworkError::Request(e)) => return Err(http_error(e)),
+            result => return result.map_err(DownloadError::Network),
+        }
+    }
+}
+
+/// Timeouts are reported without reqwest's description of where they happened
+fn http_error(error: reqwest::Error) -> DownloadError {
+    if error.is_timeout() {
+        DownloadError::Timeout
+    } else {
+        error.into()
+    }
+}
+
+fn is_transient(error: &reqwest::Error) -> bool {
+    error.is_connect()
+        || error.is_timeout()
+        || error
+            .status()
+            .is_some_and(|s| s.is_server_error() || s == StatusCode::TOO_MANY_REQUESTS)
+}
+
+/// Copies `file://` URLs, for local mirrors
+pub struct LocalFile;
+
+impl Backend for LocalFile {
+    fn fetch<'a>(
+        &'a self,
+        url: &'a str,
+        sink: &'a mut Sink<'_>,
+    ) -> BoxFuture<'a, Result<(), DownloadError>> {
+        Box::pin(async move {
+            let path = Url::parse(url)
+                .ok()
+                .and_then(|url| url.to_file_path().ok())
+                .ok_or_else(|| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        format!("Invalid file URL {url}"),
+                    )
+                })?;
+            let mut file = tokio::fs::File::open(&path).await.map_err(|e| {
+                std::io::Error::new(e.kind(), format!("Could not open {}: {e}", path.display()))
+            })?;
+            let total = file.metadata().await?.len();
+            if sink.start(None, Some(total))?.is_none() {
+                return Ok(());
+            }
+            let mut buf = vec![0; 1 << 16];
+            loop {
+                match file.read(&mut buf).await? {
+                    0 => return Ok(()),
+                    n => sink.write(&buf[..n]).await?,
+                }
+            }
+        })
+    }
+}
+
+/// Runs `curl`, which writes each file itself
+pub struct Curl {
+    network: Network,
+}
+
+impl Curl {
+    pub fn new(network: Network) -> Self {
+        Self { network }
+    }
+}
+
+impl Backend for Curl {
+    fn can_pause(&self) -> bool {
+        false
+    }
+
+    fn fetch<'a>(
+        &'a self,
+        url: &'a str,
+        sink: &'a mut Sink<'_>,
+    ) -> BoxFuture<'a, Result<(), DownloadError>> {
+        Box::pin(async move {
+            let Some(path) = sink.start(None, None)? else {
+                return Ok(());
+            };
+            let mut command = Command::new("curl");
+            command
+                .args(["--fail", "--location", "--show-error"])
+                .args(["--retry", &(MAX_ATTEMPTS - 1).to_string()])
+                .args(self.network.curl_args(url))
+                .arg("--output")
+                .arg(&path)
+                .arg(url);
+            let output = Output {
+                // The progress meter is written to stderr, along with any error
+                stream: Stream::Stderr,
+                progress: |line| {
+                    let fields: Vec<&str> = line.split_whitespace().collect();
+                    match fields.as_slice() {
+                        [_, total, _, received, ..] => {
+                            Some((parse_size(received)?, parse_size(total)?))
+                        }
+                        _ => None,
+                    }
+                },
+                error: |line| line.strip_prefix("curl: ").map(String::from),
+            };
+            run("curl", command, sink, output).await
+        })
+    }
+}
+
+/// Runs `aria2c`, which writes each file itself
+pub struct Aria2c {
+    network: Network,
+}
+
+impl Aria2c {
+    pub fn new(network: Network) -> Self {
+        Self { network }
+    }
+}
+
+impl Backend for Aria2c {
+    fn can_pause(&self) -> bool {
+        false
+    }
+
+    fn fetch<'a>(
+        &'a self,
+        url: &'a str,
+        sink: &'a mut Sink<'_>,
+    ) -> BoxFuture<'a, Result<(), DownloadError>> {
+        Box::pin(async move {
+            let Some(path) = sink.start(None, None)? else {
+                return Ok(());
+            };
+            let dir = path.parent().filter(|p| !p.as_os_str().is_empty());
+            let mut command = Command::new("aria2c");
+            command
+                .arg("--dir")
+                .arg(dir.unwrap_or(Path::new(".")))
+                .arg("--out")
+                .arg(path.file_name().unwrap_or_default())
+                .args([
+                    // The file has already been created, empty, according to the overwrite policy
+                    "--allow-overwrite=true",
+                    "--auto-file-renaming=false",
+                    "--file-allocation=none",
+                    "--console-log-level=error",
+                    "--summary-interval=0",
+                    "--enable-color=false",
+                ])
+                .arg(format!("--max-tries={MAX_ATTEMPTS}"))
+                .args(self.network.aria2c_args(url))
+                .arg(url);
+            let output = Output {
+                stream: Stream::Stdout,
+                // e.g. `[#2089b0 400KiB/1.0MiB(39%) CN:1 DL:115KiB ETA:5s]`
+                progress: |line| {
+                    let progress = line.strip_prefix("[#")?.split_whitespace().nth(1)?;
+                    let (received, total) = progress.split_once('(')?.0.split_once('/')?;
+                    Some((parse_size(received)?, parse_size(total)?))
+                },
+                // Errors end with the reason, after the error code
+                error: |line| {
+                    let (_, reason) = line.rsplit_once("errorCode=")?.1.split_once(' ')?;
+                    Some(reason.to_string())
+                },
+            };
+            run("aria2c", command, sink, output).await
+        })
+    }
+}
+
+enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// How to read the output of a program
+struct Output {
+    /// The stream progress and errors are written to
+    stream: Stream,
+    /// The bytes received and total size shown by a line
+    progress: fn(&str) -> Option<(u64, u64)>,
+    /// The error described by a line
+    error: fn(&str) -> Option<String>,
+}
+
+/// Run a program which writes the file itself, following its progress.
+/// If it fails, the last error it printed is returned
+async fn run(
+    program: &'static str,
+    mut command: Command,
+    sink: &mut Sink<'_>,
+    output: Output,
+) -> Result<(), DownloadError> {
+    // Anything on the other stream would be written over the interface or the JSON events
+    match output.stream {
+        Stream::Stdout => command.stdout(Stdio::piped()).stderr(Stdio::null()),
+        Stream::Stderr => command.stderr(Stdio::piped()).stdout(Stdio::null()),
+    };
+    let mut child = command
+        .stdin(Stdio::null())
+        // The process is stopped if the download is cancelled
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| DownloadError::Command(program, e))?;
+    let mut stream: Box<dyn AsyncRead + Unpin + Send> = match output.stream {
+        Stream::Stdout => Box::new(child.stdout.take().expect("stdout is piped")),
+        Stream::Stderr => Box::new(child.stderr.take().expect("stderr is piped")),
+    };
+
+    let mut error = None;
+    let mut line = vec![];
+    let mut buf = [0; 4096];
+    loop {
+        let n = stream.read(&mut buf).await?;
+        // Progress is redrawn using carriage returns, so they also end lines
+        for &byte in buf[..n].iter().chain((n == 0).then_some(&b'\n')) {
+            if byte != b'\r' && byte != b'\n' {
+                line.push(byte);
+                continue;
+            }
+            let text = String::from_utf8_lossy(&line);
+            let text = text.trim();
+            if let Some((bytes, total)) = (output.progress)(text) {
+                sink.progress(bytes, Some(total));
+            } else if let Some(message) = (output.error)(text) {
+                error = Some(message);
+            }
+            line.clear();
+        }
+        if n == 0 {
+            break;
+        }
+    }
+
+    let status = child
+        .wait()
+        .await
+        .map_err(|e| DownloadError::Command(program, e))?;
+    if !status.success() {
+        let message = error.unwrap_or_else(|| status.to_string());
+        return Err(DownloadError::External(program, message));
+    }
+    Ok(())
+}
+
+/// Parse sizes shown by curl and aria2c, such as `2929k`, `1.5M` and `400KiB`, which are all in powers of 1024
+fn parse_size(size: &str) -> Option<u64> {
+    let size = size.trim_end_matches("iB").trim_end_matches('B');
+    let (value, exponent) = match size.char_indices().last()? {
+        (i, c) if c.is_ascii_alphabetic() => {
+            let exponent = "kmgtp".find(c.to_ascii_lowercase())? + 1;
+            (&size[..i], exponent as i32)
+        }
+        _ => (size, 0),
+    };
+    let value: f64 = value.parse().ok()?;
+    Some((value * 1024f64.powi(exponent)) as u64)
+}
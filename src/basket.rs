@@ -0,0 +1,254 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    sync::{LazyLock, Mutex},
+};
+
+use quickget_core::data_structures::Config;
+use quickosdl::catalogue::{OSInfo, Selection};
+use ratatui::{
+    crossterm::event::{KeyEvent, MouseEvent},
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Style, Stylize},
+    text::{Line, Span},
+    widgets::{ListItem, Paragraph},
+    Frame,
+};
+use reqwest::header::CONTENT_LENGTH;
+use size::Size;
+use tokio::runtime::Runtime;
+
+use crate::{
+    app::{Action, Page},
+    download::DownloadPage,
+    events::{self, AppEvent},
+    keybinds::KeyBind,
+    keymap::{keymap, Command},
+    searchable_list::{Highlights, SearchableItem, SearchableList},
+    settings::client,
+};
+
+/// Images marked for download, possibly spanning several OSes and architectures
+static BASKET: Mutex<Vec<Selection>> = Mutex::new(Vec::new());
+/// Remote file sizes by URL. Missing entries are still being fetched, `None` if the size is unknown
+static SIZES: LazyLock<Mutex<HashMap<String, Option<u64>>>> = LazyLock::new(Default::default);
+
+/// Total size of the selection's sources, and whether every size is known
+fn selection_size(selection: &Selection, sizes: &HashMap<String, Option<u64>>) -> (u64, bool) {
+    selection
+        .sources()
+        .fold((0, true), |(total, complete), s| match sizes.get(&s.url) {
+            Some(Some(size)) => (total + size, complete),
+            _ => (total, false),
+        })
+}
+
+pub fn contains(os: &OSInfo, config: &Config) -> bool {
+    BASKET.lock().unwrap().iter().any(|e| e.matches(os, config))
+}
+
+pub fn len() -> usize {
+    BASKET.lock().unwrap().len()
+}
+
+/// Add the config to the basket, or remove it if it's already present
+pub fn toggle(os: &OSInfo, config: &Config) {
+    let mut basket = BASKET.lock().unwrap();
+    match basket.iter().position(|e| e.matches(os, config)) {
+        Some(index) => _ = basket.remove(index),
+        None => basket.push(Selection::new(os.clone(), config.clone())),
+    }
+}
+
+/// Toggle a group of configs together: add all of them unless they're all present already
+pub fn toggle_all(os: &OSInfo, configs: &[&Config]) {
+    let all_present = configs.iter().all(|c| contains(os, c));
+    for config in configs {
+        if all_present || !contains(os, config) {
+            toggle(os, config);
+        }
+    }
+}
+
+/// Keybinds shared by the pages which can add to the basket
+pub fn keybinds(can_toggle: bool) -> Vec<KeyBind> {
+    let mut binds = vec![];
+    if can_toggle {
+        binds.push(keymap().keybind(Command::ToggleBasket));
+    }
+    if len() > 0 {
+        binds.push(keymap().keybind(Command::ViewBasket));
+    }
+    binds
+}
+
+/// The size of a remote file: `None` while it's being fetched, `Some(None)` if it's unknown
+pub fn remote_size(url: &str) -> Option<Option<u64>> {
+    SIZES.lock().unwrap().get(url).copied()
+}
+
+/// Fetch the sizes of any files which aren't already known in the background
+pub fn fetch_sizes(urls: Vec<String>) {
+    std::thread::spawn(move || {
+        let rt = Runtime::new().unwrap();
+        for url in urls {
+            if SIZES.lock().unwrap().contains_key(&url) {
+                continue;
+            }
+            // The content length of a HEAD response must be read from the headers directly
+            let size = rt
+                .block_on(client().head(&url))
+                .ok()
+                .filter(|r| r.status().is_success())
+                .and_then(|r| r.headers().get(CONTENT_LENGTH)?.to_str().ok()?.parse().ok());
+            SIZES.lock().unwrap().insert(url, size);
+            events::send(AppEvent::SizeFetched);
+        }
+    });
+}
+
+fn format_size(size: u64, complete: bool) -> String {
+    match (size, complete) {
+        (0, false) => "unknown size".to_string(),
+        (size, true) => Size::from_bytes(size).to_string(),
+        (size, false) => format!("at least {}", Size::from_bytes(size)),
+    }
+}
+
+impl SearchableItem for Selection {
+    fn to_list_item(&self, _: usize, highlights: Highlights) -> ListItem<'_> {
+        let mut line = vec![Span::raw(format!("[{}] ", self.config.arch))];
+        line.extend(highlights.spans(0, &self.os.pretty_name, 0));
+        line.push(Span::raw(" "));
+        line.extend(highlights.spans(1, &self.config.release, 0));
+        if let Some(edition) = &self.config.edition {
+            line.push(Span::raw(" "));
+            line.extend(highlights.spans(2, edition, 0));
+        }
+        let (size, complete) = selection_size(self, &SIZES.lock().unwrap());
+        line.push(Span::styled(
+            format!("  ({})", format_size(size, complete)),
+            Style::new().italic(),
+        ));
+        ListItem::new(Line::from(line))
+    }
+    fn filter_fields(&self) -> Vec<Cow<'_, str>> {
+        let mut fields = vec![
+            Cow::Borrowed(self.os.pretty_name.as_str()),
+            Cow::Borrowed(&self.config.release),
+        ];
+        fields.extend(self.config.edition.as_deref().map(Cow::Borrowed));
+        fields
+    }
+}
+
+pub struct BasketPage {
+    list: SearchableList<Selection>,
+}
+
+impl BasketPage {
+    pub fn new() -> Self {
+        let entries = BASKET.lock().unwrap().clone();
+        fetch_sizes(
+            entries
+                .iter()
+                .flat_map(|e| e.sources().map(|s| s.url))
+                .collect(),
+        );
+        Self {
+            list: SearchableList::new(entries),
+        }
+    }
+
+    fn refresh(&mut self) {
+        let entries = BASKET.lock().unwrap().clone();
+        self.list.replace_items(entries, |e| {
+            (
+                e.os.name.clone(),
+                e.config.release.clone(),
+                e.config.edition.clone(),
+                e.config.arch.to_string(),
+            )
+        });
+    }
+
+    pub fn handle_key(&mut self, key: &KeyEvent) -> Option<Action> {
+        if !self.list.accepts_commands() {
+            self.list.handle_key(key);
+            return None;
+        }
+        match keymap().command(key) {
+            Some(Command::Quit) => Some(Action::Exit),
+            Some(Command::Back) => Some(Action::PrevPage),
+            Some(Command::DownloadAll) if len() > 0 => {
+                let selections = BASKET.lock().unwrap().clone();
+                Some(Action::NextPage(Page::Download(DownloadPage::new(
+                    selections,
+                ))))
+            }
+            Some(Command::RemoveFromBasket) => {
+                if let Some(entry) = self.list.selected_item() {
+                    toggle(&entry.os, &entry.config);
+                    self.refresh();
+                }
+                None
+            }
+            Some(Command::ClearBasket) => {
+                BASKET.lock().unwrap().clear();
+                self.refresh();
+                None
+            }
+            _ => {
+                self.list.handle_key(key);
+                None
+            }
+        }
+    }
+
+    pub fn accepts_commands(&self) -> bool {
+        self.list.accepts_commands()
+    }
+
+    pub fn handle_mouse(&mut self, mouse: &MouseEvent) -> Option<Action> {
+        self.list.handle_mouse(mouse);
+        None
+    }
+
+    pub fn draw(&mut self, frame: &mut Frame, area: Rect) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(1), Constraint::Min(0)])
+            .split(area);
+
+        let (total, complete) = {
+            let sizes = SIZES.lock().unwrap();
+            BASKET
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|e| selection_size(e, &sizes))
+                .fold((0, true), |(total, complete), (size, c)| {
+                    (total + size, complete && c)
+                })
+        };
+        let summary = Line::from(vec![
+            Span::styled(format!("{} images", len()), Style::new().bold()),
+            Span::raw(format!(", {} in total", format_size(total, complete))),
+        ]);
+        frame.render_widget(Paragraph::new(summary), chunks[0]);
+        self.list.draw(frame, chunks[1]);
+    }
+
+    pub fn keybinds(&self) -> Vec<KeyBind> {
+        let mut binds = self.list.keybinds(true);
+        if !self.list.is_searching() {
+            let keymap = keymap();
+            if len() > 0 {
+                binds.push(keymap.keybind(Command::DownloadAll));
+            }
+            binds.push(keymap.keybind(Command::RemoveFromBasket));
+            binds.push(keymap.keybind(Command::ClearBasket));
+        }
+        binds
+    }
+}
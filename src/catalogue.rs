@@ -0,0 +1,118 @@
+use quickget_core::{
+    data_structures::{Arch, Config, Disk, Source, WebSource, OS},
+    ConfigSearch, ConfigSearchError,
+};
+
+use crate::network::{HttpClient, NetworkError};
+
+#[derive(thiserror::Error, Debug)]
+pub enum CatalogueError {
+    #[error("{0}")]
+    Search(#[from] ConfigSearchError),
+    #[error("Could not fetch catalogue: {0}")]
+    Reqwest(#[from] reqwest::Error),
+    #[error("Could not fetch catalogue: {0}")]
+    Network(#[from] NetworkError),
+    #[error("Could not read catalogue: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid catalogue: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// Load a catalogue in the quickget_cigo JSON format from a URL or a local path,
+/// or the quickget_cigo data itself if none is given. The quickget_cigo data is fetched by quickget_core,
+/// which only uses proxies from the environment rather than the client
+pub async fn load(catalogue: Option<&str>, client: &HttpClient) -> Result<Vec<OS>, CatalogueError> {
+    let Some(catalogue) = catalogue else {
+        let search = ConfigSearch::new_without_cache().await?;
+        return Ok(search.into_os_list());
+    };
+    let data = if is_remote(catalogue) {
+        let response = client.get(catalogue).await?.error_for_status()?;
+        response.bytes().await?.to_vec()
+    } else {
+        tokio::fs::read(catalogue).await?
+    };
+    Ok(serde_json::from_slice(&data)?)
+}
+
+/// Whether a catalogue is fetched from a URL, rather than read from a local path
+pub fn is_remote(catalogue: &str) -> bool {
+    catalogue.starts_with("http://") || catalogue.starts_with("https://")
+}
+
+/// The operating systems with images which can be downloaded for an architecture,
+/// keeping only those releases
+pub fn for_arch(os_list: &[OS], arch: Arch) -> Vec<OS> {
+    os_list
+        .iter()
+        .cloned()
+        .map(|mut os| {
+            os.releases.retain(has_only_wanted_sources);
+            os.releases.retain(|c| c.arch == arch);
+            os
+        })
+        .filter(|os| !os.releases.is_empty())
+        .collect()
+}
+
+fn has_only_wanted_sources(config: &Config) -> bool {
+    (config.disk_images.is_none() || config.disk_images == Some(vec![Disk::default()]))
+        && config
+            .iso
+            .iter()
+            .chain(config.img.iter())
+            .all(|s| matches!(s, Source::Web(_)))
+}
+
+/// The files of a configuration which can be downloaded directly
+pub fn web_sources(config: &Config) -> impl Iterator<Item = WebSource> + use<'_> {
+    config
+        .iso
+        .iter()
+        .chain(config.img.iter())
+        .filter_map(|s| match s {
+            Source::Web(web) => Some(web.clone()),
+            _ => None,
+        })
+}
+
+/// Identifies the OS a selection belongs to
+#[derive(Clone)]
+pub struct OSInfo {
+    pub name: String,
+    pub pretty_name: String,
+}
+
+impl From<&OS> for OSInfo {
+    fn from(os: &OS) -> Self {
+        Self {
+            name: os.name.clone(),
+            pretty_name: os.pretty_name.clone(),
+        }
+    }
+}
+
+/// A single image configuration, along with the OS it belongs to
+#[derive(Clone)]
+pub struct Selection {
+    pub os: OSInfo,
+    pub config: Config,
+}
+
+impl Selection {
+    pub fn new(os: OSInfo, config: Config) -> Self {
+        Self { os, config }
+    }
+
+    pub fn matches(&self, os: &OSInfo, config: &Config) -> bool {
+        self.os.name == os.name
+            && self.config.release == config.release
+            && self.config.edition == config.edition
+            && self.config.arch == config.arch
+    }
+
+    pub fn sources(&self) -> impl Iterator<Item = WebSource> + use<'_> {
+        web_sources(&self.config)
+    }
+}
@@ -0,0 +1,116 @@
+use std::{
+    fs::File,
+    io::{self, Read},
+    path::Path,
+};
+
+use md5::Md5;
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
+
+#[derive(thiserror::Error, Debug)]
+#[error("Checksum {actual} does not match expected value {expected}")]
+pub struct ChecksumMismatch {
+    pub actual: String,
+    pub expected: String,
+}
+
+/// Name of the hash algorithm a checksum was produced with, based on its length
+pub fn algorithm_name(checksum: &str) -> Option<&'static str> {
+    ChecksumAlgorithm::for_checksum(checksum).map(|a| a.name())
+}
+
+/// Whether an existing file matches a checksum. Checksums of unknown algorithms can't be verified, so always match
+pub fn verify_file(path: &Path, checksum: &str) -> io::Result<bool> {
+    let Some(mut verification) = ChecksumVerification::new(checksum.to_lowercase()) else {
+        return Ok(true);
+    };
+    verification.write_file(path)?;
+    Ok(verification.validate().is_ok())
+}
+
+/// Hashes data as it's written, comparing the result against an expected checksum
+pub struct ChecksumVerification {
+    expected_checksum: String,
+    algorithm: ChecksumAlgorithm,
+}
+
+enum ChecksumAlgorithm {
+    Md5(Md5),
+    Sha1(Sha1),
+    Sha256(Sha256),
+    Sha512(Sha512),
+}
+
+impl ChecksumAlgorithm {
+    fn for_checksum(checksum: &str) -> Option<Self> {
+        match checksum.len() {
+            32 => Some(ChecksumAlgorithm::Md5(Md5::new())),
+            40 => Some(ChecksumAlgorithm::Sha1(Sha1::new())),
+            64 => Some(ChecksumAlgorithm::Sha256(Sha256::new())),
+            128 => Some(ChecksumAlgorithm::Sha512(Sha512::new())),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            ChecksumAlgorithm::Md5(_) => "MD5",
+            ChecksumAlgorithm::Sha1(_) => "SHA-1",
+            ChecksumAlgorithm::Sha256(_) => "SHA-256",
+            ChecksumAlgorithm::Sha512(_) => "SHA-512",
+        }
+    }
+}
+
+impl ChecksumVerification {
+    /// Verify data against a lowercase hex checksum, if its algorithm is known
+    pub fn new(expected_checksum: String) -> Option<Self> {
+        let algorithm = ChecksumAlgorithm::for_checksum(&expected_checksum)?;
+        Some(Self {
+            expected_checksum,
+            algorithm,
+        })
+    }
+
+    pub fn algorithm(&self) -> &'static str {
+        self.algorithm.name()
+    }
+
+    pub fn write_chunk(&mut self, data: &[u8]) {
+        match &mut self.algorithm {
+            ChecksumAlgorithm::Md5(a) => a.update(data),
+            ChecksumAlgorithm::Sha1(a) => a.update(data),
+            ChecksumAlgorithm::Sha256(a) => a.update(data),
+            ChecksumAlgorithm::Sha512(a) => a.update(data),
+        }
+    }
+
+    /// Hash the whole of an existing file
+    pub fn write_file(&mut self, path: &Path) -> io::Result<()> {
+        let mut file = File::open(path)?;
+        let mut buf = vec![0; 1 << 16];
+        loop {
+            match file.read(&mut buf)? {
+                0 => return Ok(()),
+                n => self.write_chunk(&buf[..n]),
+            }
+        }
+    }
+
+    pub fn validate(self) -> Result<(), ChecksumMismatch> {
+        let actual_checksum = match self.algorithm {
+            ChecksumAlgorithm::Md5(a) => format!("{:x}", a.finalize()),
+            ChecksumAlgorithm::Sha1(a) => format!("{:x}", a.finalize()),
+            ChecksumAlgorithm::Sha256(a) => format!("{:x}", a.finalize()),
+            ChecksumAlgorithm::Sha512(a) => format!("{:x}", a.finalize()),
+        };
+        if actual_checksum != self.expected_checksum {
+            return Err(ChecksumMismatch {
+                actual: actual_checksum,
+                expected: self.expected_checksum,
+            });
+        }
+        Ok(())
+    }
+}
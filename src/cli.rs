@@ -0,0 +1,160 @@
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand, ValueEnum};
+use quickget_core::data_structures::Arch;
+use quickosdl::{backend::BackendKind, downloader::OverwritePolicy};
+
+use crate::{arch_selection::parse_arch, clipboard::ClipboardMethod, lock::DEFAULT_MANIFEST};
+
+/// Quickly find and download images of various operating systems
+#[derive(Parser)]
+#[command(version, about)]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// Read configuration from this file rather than the default location
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+    /// Print results as JSON, and download progress as newline-delimited JSON events, rather than using the interface
+    #[arg(long, global = true)]
+    pub json: bool,
+    #[command(flatten)]
+    pub overrides: Overrides,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Validate the configuration file and print the effective configuration
+    Config,
+    /// Write a manifest pinning the URLs, file names and checksums of images
+    Lock {
+        /// Images to lock, as os/release[/edition][@arch], e.g. ubuntu/24.04/desktop@x86_64
+        #[arg(required = true)]
+        images: Vec<String>,
+        /// Where to write the manifest
+        #[arg(short, long, default_value = DEFAULT_MANIFEST)]
+        manifest: PathBuf,
+    },
+    /// Download any files from a manifest which are missing or don't match their checksums
+    Sync {
+        #[arg(default_value = DEFAULT_MANIFEST)]
+        manifest: PathBuf,
+    },
+}
+
+/// Options which take precedence over the configuration file
+#[derive(clap::Args)]
+pub struct Overrides {
+    /// Architecture to list images for. Defaults to the configured or host architecture
+    #[arg(short, long, global = true, value_parser = parse_arch)]
+    pub arch: Option<Arch>,
+    /// Directory to save downloaded images to
+    #[arg(short, long, global = true)]
+    pub output_dir: Option<PathBuf>,
+    /// Template for downloaded file names, using {os}, {release}, {edition}, {arch}, {filename} and {ext}
+    #[arg(long, global = true)]
+    pub filename_template: Option<String>,
+    /// What to do when a downloaded file already exists
+    #[arg(long, global = true)]
+    pub overwrite: Option<OverwriteArg>,
+    /// Maximum number of simultaneous downloads
+    #[arg(short = 'j', long, global = true)]
+    pub concurrency: Option<usize>,
+    /// Total download speed limit per second, e.g. 10MiB
+    #[arg(long, global = true)]
+    pub rate_limit: Option<String>,
+    /// How files are downloaded. file:// URLs from a local catalogue are always copied directly
+    #[arg(long, global = true)]
+    pub backend: Option<BackendArg>,
+    /// URL or path of a JSON catalogue to use instead of the quickget_cigo data
+    #[arg(long, global = true)]
+    pub catalogue: Option<String>,
+    /// Colour theme: dark, light, high-contrast, no-color, or a theme from the configuration file
+    #[arg(long, global = true)]
+    pub theme: Option<String>,
+    /// Leave the mouse to the terminal, e.g. to select text, rather than using it to click and scroll
+    #[arg(long, global = true)]
+    pub no_mouse: bool,
+    /// How text copied from the URL list is delivered
+    #[arg(long, global = true)]
+    pub clipboard: Option<ClipboardMethod>,
+    /// File to write a JSON summary of the downloads to
+    #[arg(long, global = true)]
+    pub report_file: Option<PathBuf>,
+    /// Seconds to wait for a connection to a server
+    #[arg(long, global = true, value_name = "SECONDS")]
+    pub connect_timeout: Option<u64>,
+    /// Seconds to wait for more data before a stalled download fails
+    #[arg(long, global = true, value_name = "SECONDS")]
+    pub read_timeout: Option<u64>,
+    /// User-Agent sent with every request
+    #[arg(long, global = true)]
+    pub user_agent: Option<String>,
+    /// Extra header for a host and its subdomains, e.g. "example.com=Cookie: session=1". May be repeated
+    #[arg(long = "header", global = true, value_name = "HOST=NAME:VALUE", value_parser = parse_header)]
+    pub headers: Vec<HostHeader>,
+}
+
+// The library's options are mirrored here, so the library doesn't depend on clap
+
+/// What to do when a downloaded file already exists
+#[derive(Clone, Copy, ValueEnum)]
+pub enum OverwriteArg {
+    /// Fail the download
+    Never,
+    /// Replace the existing file
+    Overwrite,
+    /// Keep the existing file and skip the download
+    Skip,
+}
+
+impl From<OverwriteArg> for OverwritePolicy {
+    fn from(overwrite: OverwriteArg) -> Self {
+        match overwrite {
+            OverwriteArg::Never => OverwritePolicy::Never,
+            OverwriteArg::Overwrite => OverwritePolicy::Overwrite,
+            OverwriteArg::Skip => OverwritePolicy::Skip,
+        }
+    }
+}
+
+/// How files are downloaded
+#[derive(Clone, Copy, ValueEnum)]
+pub enum BackendArg {
+    /// Download within quickosdl
+    Builtin,
+    /// Run aria2c for each file
+    Aria2c,
+    /// Run curl for each file
+    Curl,
+}
+
+impl From<BackendArg> for BackendKind {
+    fn from(backend: BackendArg) -> Self {
+        match backend {
+            BackendArg::Builtin => BackendKind::Builtin,
+            BackendArg::Aria2c => BackendKind::Aria2c,
+            BackendArg::Curl => BackendKind::Curl,
+        }
+    }
+}
+
+/// A header to send to a host
+#[derive(Clone)]
+pub struct HostHeader {
+    pub host: String,
+    pub name: String,
+    pub value: String,
+}
+
+fn parse_header(header: &str) -> Result<HostHeader, String> {
+    let (host, header) = header.split_once('=').ok_or("expected HOST=NAME:VALUE")?;
+    let (name, value) = header
+        .split_once(':')
+        .ok_or("expected a header as NAME:VALUE")?;
+    Ok(HostHeader {
+        host: host.trim().to_string(),
+        name: name.trim().to_string(),
+        value: value.trim().to_string(),
+    })
+}
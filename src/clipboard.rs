@@ -0,0 +1,60 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use base64::{prelude::BASE64_STANDARD, Engine};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::settings::settings;
+
+/// Longest encoded text sent with OSC 52. Many terminals silently drop longer sequences
+const MAX_OSC52_LEN: usize = 100_000;
+
+/// How copied text is delivered
+#[derive(Default, Clone, Copy, PartialEq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum ClipboardMethod {
+    /// Ask the terminal to set the clipboard with an OSC 52 escape sequence, which also works over SSH
+    #[default]
+    Osc52,
+    /// Always write to a file, for terminals without OSC 52 support
+    File,
+}
+
+/// Where copied text ended up
+pub enum Copied {
+    Clipboard,
+    File(PathBuf),
+}
+
+/// Copy text to the clipboard, falling back to a file if it's too long or the terminal can't be written to
+pub fn copy(text: &str) -> io::Result<Copied> {
+    if settings().clipboard == ClipboardMethod::Osc52 {
+        let encoded = BASE64_STANDARD.encode(text);
+        if encoded.len() <= MAX_OSC52_LEN && write_osc52(&encoded).is_ok() {
+            return Ok(Copied::Clipboard);
+        }
+    }
+    let path = fallback_path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, text)?;
+    Ok(Copied::File(path))
+}
+
+fn write_osc52(encoded: &str) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{encoded}\x07")?;
+    stdout.flush()
+}
+
+fn fallback_path() -> PathBuf {
+    dirs::cache_dir()
+        .map(|dir| dir.join("quickosdl"))
+        .unwrap_or_else(std::env::temp_dir)
+        .join("clipboard.txt")
+}
@@ -1,5 +1,5 @@
 use ratatui::{
-    crossterm::event::{KeyCode, KeyEvent},
+    crossterm::event::KeyEvent,
     layout::{Constraint, Flex, Layout, Rect},
     widgets::Paragraph,
     Frame,
@@ -13,11 +13,8 @@ impl CompletePage {
     pub fn new() -> Self {
         Self {}
     }
-    pub fn handle_key(&mut self, key: &KeyEvent) -> Option<Action> {
-        match key.code {
-            KeyCode::Null => None,
-            _ => Some(Action::Exit),
-        }
+    pub fn handle_key(&mut self, _: &KeyEvent) -> Option<Action> {
+        Some(Action::Exit)
     }
     pub fn draw(&mut self, frame: &mut Frame, area: Rect) {
         let [centered] = Layout::vertical([Constraint::Length(1)])
@@ -1,25 +1,23 @@
 use std::{
     borrow::Cow,
-    fs::File,
-    io::Write,
-    sync::{
-        atomic::{AtomicU64, Ordering},
-        Arc,
-    },
+    sync::{Arc, Mutex},
 };
 
-use md5::Md5;
 use quickget_core::data_structures::WebSource;
+use quickosdl::{
+    catalogue::Selection,
+    downloader::{
+        display_name, DownloadError, Downloader, Event, OverwritePolicy, Saved, Transfer,
+    },
+};
 use ratatui::{
-    crossterm::event::{KeyCode, KeyEvent},
-    layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Style},
+    crossterm::event::{KeyEvent, MouseButton, MouseEvent, MouseEventKind},
+    layout::{Constraint, Direction, Flex, Layout, Position, Rect},
+    style::{Style, Stylize},
     text::Span,
     widgets::Gauge,
     Frame,
 };
-use sha1::Sha1;
-use sha2::{Digest, Sha256, Sha512};
 use size::Size;
 use tokio::{runtime::Runtime, task::JoinHandle};
 
@@ -27,42 +25,79 @@ use crate::{
     app::{Action, Page},
     complete::CompletePage,
     error_display::ErrorDisplay,
+    events::{self, AppEvent},
     keybinds::KeyBind,
+    library,
+    report::{self, DownloadReporter, Report},
+    settings::{client, settings},
+    summary::{self, DownloadResult, ResultStatus},
+    theme::theme,
 };
 
+/// Width of the pause and cancel controls beside each gauge
+const CONTROLS_WIDTH: u16 = 20;
+
 pub struct DownloadPage {
-    rt: Runtime,
-    has_failed_download: bool,
+    /// Runs the downloads, which are stopped when the page is dropped
+    _rt: Runtime,
     downloads: Vec<Download>,
 }
 
 impl DownloadPage {
-    pub fn new(sources: impl Iterator<Item = WebSource>) -> Self {
+    pub fn new(selections: Vec<Selection>) -> Self {
+        Self::with_overwrite(selections, settings().overwrite)
+    }
+
+    /// Download with a policy for existing files other than the configured one
+    pub fn with_overwrite(selections: Vec<Selection>, overwrite: OverwritePolicy) -> Self {
         let rt = Runtime::new().unwrap();
-        let downloads = sources.into_iter().map(|s| Download::new(&rt, s)).collect();
-        Self {
-            rt,
-            has_failed_download: false,
-            downloads,
-        }
+        let settings = settings();
+        let downloader = Downloader::new(
+            settings.backend.backend(client(), &settings.network),
+            overwrite,
+            settings.concurrency,
+            settings.rate_limit().ok().flatten(),
+        )
+        .with_local_files(settings.local_catalogue());
+        let downloads = selections
+            .iter()
+            .flat_map(|selection| selection.sources().map(move |s| (selection, s)))
+            .enumerate()
+            .map(|(id, (selection, source))| Download::new(&rt, id, source, selection, &downloader))
+            .collect();
+        Self { _rt: rt, downloads }
     }
 
-    pub fn handle_key(&mut self, key: &KeyEvent) -> Option<Action> {
-        if let KeyCode::Null = key.code {
-            return None;
-        }
+    pub fn handle_key(&mut self, _: &KeyEvent) -> Option<Action> {
+        self.next_page()
+    }
+
+    /// Record finished downloads, and leave the page once they've all stopped or one has failed
+    pub fn update(&mut self) -> Option<Action> {
+        self.record_outcomes();
+        self.next_page()
+    }
+
+    /// The page to show once every download has completed or been cancelled, or as soon as one fails
+    fn next_page(&mut self) -> Option<Action> {
         let mut all_complete = true;
         let mut errors = vec![];
         for download in self.downloads.iter() {
             match &download.status {
                 DownloadStatus::Failed(e) => errors.push(e.to_string()),
                 DownloadStatus::InProgress => all_complete = false,
-                DownloadStatus::Complete => {}
+                DownloadStatus::Complete | DownloadStatus::Cancelled => {}
             }
         }
+        let all_cancelled = self
+            .downloads
+            .iter()
+            .all(|d| matches!(d.status, DownloadStatus::Cancelled));
         if !errors.is_empty() {
-            self.downloads.iter().for_each(Download::cancel);
+            self.downloads.iter_mut().for_each(Download::cancel);
             Some(Action::NextPage(Page::Error(ErrorDisplay::new(errors))))
+        } else if all_cancelled {
+            Some(Action::PrevPage)
         } else if all_complete {
             Some(Action::NextPage(Page::Complete(CompletePage::new())))
         } else {
@@ -70,33 +105,88 @@ impl DownloadPage {
         }
     }
 
+    /// Record the outcome of downloads which have finished
+    pub fn record_outcomes(&mut self) {
+        for download in &mut self.downloads {
+            if !matches!(download.status, DownloadStatus::InProgress) {
+                continue;
+            }
+            let Some(outcome) = download.outcome.lock().unwrap().take() else {
+                continue;
+            };
+            let result = match &outcome {
+                Ok(saved) => {
+                    library::record(
+                        &download.selection,
+                        download.checksum.as_deref(),
+                        &saved.path,
+                        !saved.skipped,
+                    );
+                    DownloadResult {
+                        status: if saved.skipped {
+                            ResultStatus::Skipped
+                        } else {
+                            ResultStatus::Completed
+                        },
+                        path: Some(saved.path.clone()),
+                        ..download.result(ResultStatus::Completed)
+                    }
+                }
+                Err(e) => DownloadResult {
+                    kind: Some(e.kind()),
+                    error: Some(e.to_string()),
+                    ..download.result(ResultStatus::Failed)
+                },
+            };
+            summary::record(result);
+            download.status = match outcome {
+                Ok(_) => DownloadStatus::Complete,
+                Err(e) => DownloadStatus::Failed(e),
+            };
+        }
+    }
+
+    /// Whether every download has stopped, successfully or not
+    pub fn is_finished(&self) -> bool {
+        !self
+            .downloads
+            .iter()
+            .any(|d| matches!(d.status, DownloadStatus::InProgress))
+    }
+
+    /// Clicking a download's controls pauses, resumes or cancels it
+    pub fn handle_mouse(&mut self, mouse: &MouseEvent) -> Option<Action> {
+        if mouse.kind != MouseEventKind::Down(MouseButton::Left) {
+            return None;
+        }
+        let position = Position::new(mouse.column, mouse.row);
+        for download in &mut self.downloads {
+            if !matches!(download.status, DownloadStatus::InProgress) {
+                continue;
+            }
+            if download.pause_area.contains(position) {
+                download.transfer.toggle_pause();
+            } else if download.cancel_area.contains(position) {
+                download.cancel();
+            }
+        }
+        None
+    }
+
     pub fn draw(&mut self, frame: &mut Frame, area: Rect) {
         let chunks = Layout::default()
             .direction(Direction::Vertical)
             .constraints(self.downloads.iter().map(|_| Constraint::Max(5)))
             .split(area);
         self.downloads.iter_mut().enumerate().for_each(|(i, d)| {
-            if let DownloadStatus::InProgress = d.status {
-                if d.handle.as_ref().unwrap().is_finished() {
-                    let handle = d.handle.take().unwrap();
-                    self.rt.block_on(async {
-                        let result = handle.await;
-                        d.status = match result {
-                            Ok(Err(e)) => {
-                                self.has_failed_download = true;
-                                DownloadStatus::Failed(e)
-                            }
-                            Ok(_) => DownloadStatus::Complete,
-                            Err(e) => panic!("Error spawning thread: {:?}", e),
-                        };
-                    })
-                }
-            }
-            let (total, current) = (
-                d.total_size.load(Ordering::Relaxed),
-                d.current_size.load(Ordering::Relaxed),
-            );
-            let ratio = if !matches!(d.status, DownloadStatus::InProgress) {
+            let [gauge_area, controls_area] =
+                Layout::horizontal([Constraint::Min(0), Constraint::Length(CONTROLS_WIDTH)])
+                    .areas(chunks[i]);
+            let (total, current) = (d.transfer.total(), d.transfer.bytes());
+            let ratio = if matches!(
+                d.status,
+                DownloadStatus::Complete | DownloadStatus::Failed(_)
+            ) {
                 1.0
             } else if total == 0 {
                 0.0
@@ -104,165 +194,201 @@ impl DownloadPage {
                 current as f64 / total as f64
             };
 
-            let (color, text, text_color) = match &d.status {
+            let theme = theme();
+            let (style, text, label_style) = match &d.status {
                 DownloadStatus::Failed(e) => (
-                    Color::Red,
-                    Cow::Owned(format!("Download Failed: {e}")),
-                    Color::Black,
+                    theme.failed,
+                    Cow::Owned(format!("{}: Download Failed: {e}", d.name)),
+                    theme.failed_label,
                 ),
                 DownloadStatus::InProgress => (
-                    Color::Blue,
+                    theme.in_progress,
                     Cow::Owned(format!(
-                        "{:.2}% ({}/{})",
+                        "{}: {}{:.2}% ({}/{})",
+                        d.name,
+                        if d.transfer.is_paused() {
+                            "Paused at "
+                        } else {
+                            ""
+                        },
                         ratio * 100.0,
                         Size::from_bytes(current),
                         Size::from_bytes(total)
                     )),
-                    Color::White,
+                    theme.in_progress_label,
                 ),
                 DownloadStatus::Complete => (
-                    Color::Green,
-                    Cow::Owned(format!("Download complete ({})", Size::from_bytes(current))),
-                    Color::Black,
+                    theme.complete,
+                    Cow::Owned(format!(
+                        "{}: Download complete ({})",
+                        d.name,
+                        Size::from_bytes(current)
+                    )),
+                    theme.complete_label,
+                ),
+                DownloadStatus::Cancelled => (
+                    theme.failed,
+                    Cow::Owned(format!("{}: Download cancelled", d.name)),
+                    theme.failed_label,
                 ),
             };
-            let text = Span::styled(text, Style::default().fg(text_color));
+            let text = Span::styled(text, label_style);
 
-            let gauge = Gauge::default().ratio(ratio).gauge_style(color).label(text);
-            frame.render_widget(gauge, chunks[i]);
+            let gauge = Gauge::default().ratio(ratio).gauge_style(style).label(text);
+            frame.render_widget(gauge, gauge_area);
+
+            (d.pause_area, d.cancel_area) = Default::default();
+            if let DownloadStatus::InProgress = d.status {
+                let pause = if d.transfer.is_paused() {
+                    "[Resume]"
+                } else {
+                    "[Pause]"
+                };
+                let row = Rect {
+                    y: controls_area.y + controls_area.height / 2,
+                    height: 1,
+                    ..controls_area
+                };
+                let pause_width = if d.can_pause { 9 } else { 0 };
+                let [pause_area, cancel_area] =
+                    Layout::horizontal([Constraint::Length(pause_width), Constraint::Length(8)])
+                        .spacing(1)
+                        .flex(Flex::Center)
+                        .areas(row);
+                if d.can_pause {
+                    frame.render_widget(Span::styled(pause, Style::new().bold()), pause_area);
+                    d.pause_area = pause_area;
+                }
+                frame.render_widget(Span::styled("[Cancel]", Style::new().bold()), cancel_area);
+                d.cancel_area = cancel_area;
+            }
         });
     }
 
     pub fn keybinds(&self) -> Vec<KeyBind> {
-        if self.has_failed_download {
-            vec![KeyBind::single_key("Any key", "Exit downloads")]
-        } else {
-            vec![]
-        }
+        vec![]
     }
 }
 
-#[allow(dead_code)]
-#[derive(thiserror::Error, Debug)]
-enum DownloadError {
-    #[error("{0}")]
-    Reqwest(#[from] reqwest::Error),
-    #[error("{0}")]
-    Io(#[from] std::io::Error),
-    #[error("Checksum {0} does not match expected value {1}")]
-    NonMatchingChecksum(String, String),
+impl Drop for DownloadPage {
+    /// Downloads still running are stopped along with the page's runtime, and their partial files removed
+    fn drop(&mut self) {
+        self.record_outcomes();
+        for download in &self.downloads {
+            if let DownloadStatus::InProgress = download.status {
+                download.handle.abort();
+                download.transfer.remove_partial_file();
+                summary::record(download.result(ResultStatus::Aborted));
+            }
+        }
+    }
 }
 
 enum DownloadStatus {
     Failed(DownloadError),
     Complete,
+    Cancelled,
     InProgress,
 }
 
 struct Download {
+    id: usize,
+    name: String,
     status: DownloadStatus,
-    handle: Option<JoinHandle<Result<(), DownloadError>>>,
-    current_size: Arc<AtomicU64>,
-    total_size: Arc<AtomicU64>,
+    handle: JoinHandle<()>,
+    /// Set by the download task when it finishes
+    outcome: Arc<Mutex<Option<Result<Saved, DownloadError>>>>,
+    transfer: Arc<Transfer>,
+    /// Whether the backend can pause the download, so the pause control is shown
+    can_pause: bool,
+    /// What the file is part of, and its checksum, for the library
+    selection: Selection,
+    checksum: Option<String>,
+    // Positions of the controls from the last draw
+    pause_area: Rect,
+    cancel_area: Rect,
 }
 
 impl Download {
-    fn new(rt: &Runtime, source: WebSource) -> Self {
-        let total_size = Arc::new(AtomicU64::new(0));
-        let current_size = Arc::new(AtomicU64::new(0));
+    fn new(
+        rt: &Runtime,
+        id: usize,
+        source: WebSource,
+        selection: &Selection,
+        downloader: &Downloader,
+    ) -> Self {
+        let name = display_name(&source);
+        let transfer = Arc::new(Transfer::default());
+        let outcome = Arc::new(Mutex::new(None));
+        let checksum = source.checksum.clone();
+        let can_pause = downloader.can_pause(&source.url);
 
-        let as_total_size = total_size.clone();
-        let as_current_size = current_size.clone();
+        let as_transfer = transfer.clone();
+        let as_outcome = outcome.clone();
+        let as_selection = selection.clone();
+        let downloader = downloader.clone();
         let handle = rt.spawn(async move {
-            let mut verification = source.checksum.and_then(ChecksumVerification::new);
-
-            let mut response = reqwest::get(&source.url).await?;
-            let size = response.content_length().unwrap_or(0);
-            as_total_size.store(size, Ordering::Relaxed);
-
-            let filename = source
-                .file_name
-                .as_deref()
-                .unwrap_or_else(|| response.url().path_segments().unwrap().last().unwrap());
-            let mut file = File::create_new(filename)?;
-
-            while let Some(chunk) = response.chunk().await? {
-                file.write_all(&chunk)?;
-                if let Some(verification) = verification.as_mut() {
-                    verification.write_chunk(&chunk);
-                }
-                as_current_size.fetch_add(chunk.len() as u64, Ordering::Relaxed);
-            }
-
-            if let Some(verification) = verification {
-                verification.validate()?;
+            let mut reporter = DownloadReporter::new(id, &source.url);
+            let result = downloader
+                .download(
+                    &source,
+                    |name| settings().output_path(name, &as_selection),
+                    &as_transfer,
+                    |event| {
+                        if let Event::Progress { .. } = event {
+                            events::send(AppEvent::DownloadProgress);
+                        }
+                        reporter.event(&event);
+                    },
+                )
+                .await;
+            if let Err(e) = &result {
+                report::emit(Report::Failed {
+                    id,
+                    kind: e.kind(),
+                    error: e.to_string(),
+                });
             }
-            Ok(())
+            *as_outcome.lock().unwrap() = Some(result);
+            events::send(AppEvent::DownloadFinished);
         });
 
         Self {
+            id,
+            name,
             status: DownloadStatus::InProgress,
-            handle: Some(handle),
-            current_size,
-            total_size,
-        }
-    }
-    fn cancel(&self) {
-        if let Some(handle) = self.handle.as_ref() {
-            handle.abort();
+            handle,
+            outcome,
+            transfer,
+            can_pause,
+            selection: selection.clone(),
+            checksum,
+            pause_area: Rect::default(),
+            cancel_area: Rect::default(),
         }
     }
-}
-
-struct ChecksumVerification {
-    expected_checksum: String,
-    algorithm: ChecksumAlgorithm,
-}
-
-enum ChecksumAlgorithm {
-    Md5(Md5),
-    Sha1(Sha1),
-    Sha256(Sha256),
-    Sha512(Sha512),
-}
-
-impl ChecksumVerification {
-    fn new(expected_checksum: String) -> Option<Self> {
-        let algorithm = match expected_checksum.len() {
-            32 => ChecksumAlgorithm::Md5(Md5::new()),
-            40 => ChecksumAlgorithm::Sha1(Sha1::new()),
-            64 => ChecksumAlgorithm::Sha256(Sha256::new()),
-            128 => ChecksumAlgorithm::Sha512(Sha512::new()),
-            _ => return None,
-        };
-        Some(Self {
-            expected_checksum,
-            algorithm,
-        })
-    }
-
-    fn write_chunk(&mut self, data: &[u8]) {
-        match &mut self.algorithm {
-            ChecksumAlgorithm::Md5(a) => a.update(data),
-            ChecksumAlgorithm::Sha1(a) => a.update(data),
-            ChecksumAlgorithm::Sha256(a) => a.update(data),
-            ChecksumAlgorithm::Sha512(a) => a.update(data),
+    /// Stop the download and remove its partial file, unless it has already finished
+    fn cancel(&mut self) {
+        if !matches!(self.status, DownloadStatus::InProgress)
+            || self.outcome.lock().unwrap().is_some()
+        {
+            return;
         }
+        self.handle.abort();
+        self.transfer.remove_partial_file();
+        self.status = DownloadStatus::Cancelled;
+        summary::record(self.result(ResultStatus::Cancelled));
     }
 
-    fn validate(self) -> Result<(), DownloadError> {
-        let actual_checksum = match self.algorithm {
-            ChecksumAlgorithm::Md5(a) => format!("{:x}", a.finalize()),
-            ChecksumAlgorithm::Sha1(a) => format!("{:x}", a.finalize()),
-            ChecksumAlgorithm::Sha256(a) => format!("{:x}", a.finalize()),
-            ChecksumAlgorithm::Sha512(a) => format!("{:x}", a.finalize()),
-        };
-        if actual_checksum != self.expected_checksum {
-            return Err(DownloadError::NonMatchingChecksum(
-                actual_checksum,
-                self.expected_checksum,
-            ));
+    fn result(&self, status: ResultStatus) -> DownloadResult {
+        DownloadResult {
+            id: self.id,
+            name: self.name.clone(),
+            status,
+            path: None,
+            bytes: self.transfer.bytes(),
+            kind: None,
+            error: None,
         }
-        Ok(())
     }
 }
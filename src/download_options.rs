@@ -1,71 +1,155 @@
 use std::borrow::Cow;
 
-use quickget_core::data_structures::{Config, Source, WebSource};
+use itertools::Itertools;
+use quickget_core::data_structures::Config;
+use quickosdl::catalogue::{OSInfo, Selection};
 use ratatui::{
-    crossterm::event::{KeyCode, KeyEvent},
-    text::Span,
+    crossterm::event::{KeyEvent, MouseEvent},
+    text::Line,
     widgets::ListItem,
 };
 
 use crate::{
     app::{Action, Page},
+    basket::{self, BasketPage},
     download::DownloadPage,
+    export::{self, ExportFormat},
     keybinds::KeyBind,
-    searchable_list::{SearchableItem, SearchableList},
+    keymap::{keymap, Command},
+    searchable_list::{Highlights, SearchableItem, SearchableList},
+    state::{update_state, RecentSelection},
+    status::Status,
     url_list::UrlList,
 };
 
-const DOWNLOAD_OPTIONS: [DownloadOption; 2] = [DownloadOption::Download, DownloadOption::ListUrls];
+const DOWNLOAD_OPTIONS: [DownloadOption; 7] = [
+    DownloadOption::Download,
+    DownloadOption::ListUrls,
+    DownloadOption::Export(ExportFormat::Aria2),
+    DownloadOption::Export(ExportFormat::Metalink),
+    DownloadOption::Export(ExportFormat::CurlScript),
+    DownloadOption::Export(ExportFormat::WgetScript),
+    DownloadOption::Export(ExportFormat::Checksums),
+];
 
 impl SearchableItem for DownloadOption {
-    fn to_list_item(&self, _: usize) -> ListItem<'_> {
-        ListItem::from(Span::raw(self.as_ref()))
+    fn to_list_item(&self, _: usize, highlights: Highlights) -> ListItem<'_> {
+        ListItem::from(Line::from(highlights.spans(0, self.as_ref(), 0)))
     }
-    fn to_filter(&self) -> Cow<'_, str> {
-        Cow::Borrowed(self.as_ref())
+    fn filter_fields(&self) -> Vec<Cow<'_, str>> {
+        vec![Cow::Borrowed(self.as_ref())]
     }
 }
 
 pub struct DownloadOptions {
+    os: OSInfo,
     config: Config,
     list: SearchableList<DownloadOption>,
+    /// Result of the last export
+    status: Option<Status>,
 }
 
 impl DownloadOptions {
-    pub fn new(config: Config) -> Self {
+    pub fn new(os: OSInfo, config: Config) -> Self {
         Self {
+            os,
             config,
             list: SearchableList::new(DOWNLOAD_OPTIONS),
+            status: None,
         }
     }
 
+    fn record_recent(&self) {
+        update_state(|state| {
+            state.push_recent(RecentSelection {
+                arch: self.config.arch.to_string(),
+                os: self.os.name.clone(),
+                release: self.config.release.clone(),
+                edition: self.config.edition.clone(),
+            })
+        });
+    }
+
     pub fn handle_key(&mut self, key: &KeyEvent) -> Option<Action> {
-        match key.code {
-            KeyCode::Char('q') if !self.list.is_searching() => Some(Action::Exit),
-            KeyCode::Char('h') if !self.list.is_searching() => Some(Action::PrevPage),
-            _ => self.list.handle_key(key).map(|option| match option {
-                DownloadOption::Download => Action::NextPage(Page::Download(DownloadPage::new(
-                    extract_sources(&self.config),
-                ))),
-                DownloadOption::ListUrls => Action::NextPage(Page::UrlList(UrlList::new(
-                    sources_to_urls(extract_sources(&self.config)),
-                ))),
-            }),
+        match keymap()
+            .command(key)
+            .filter(|_| self.list.accepts_commands())
+        {
+            Some(Command::Quit) => Some(Action::Exit),
+            Some(Command::Back) => Some(Action::PrevPage),
+            Some(Command::ToggleBasket) => {
+                basket::toggle(&self.os, &self.config);
+                None
+            }
+            Some(Command::ViewBasket) if basket::len() > 0 => {
+                Some(Action::NextPage(Page::Basket(BasketPage::new())))
+            }
+            _ => {
+                let option = *self.list.handle_key(key)?;
+                self.open(option)
+            }
+        }
+    }
+
+    pub fn selection(&self) -> Vec<String> {
+        let mut selection = vec![self.os.pretty_name.clone(), self.config.release.clone()];
+        selection.extend(self.config.edition.clone());
+        selection
+    }
+
+    pub fn accepts_commands(&self) -> bool {
+        self.list.accepts_commands()
+    }
+
+    pub fn handle_mouse(&mut self, mouse: &MouseEvent) -> Option<Action> {
+        let option = *self.list.handle_mouse(mouse)?;
+        self.open(option)
+    }
+
+    fn open(&mut self, option: DownloadOption) -> Option<Action> {
+        self.record_recent();
+        let selection = Selection::new(self.os.clone(), self.config.clone());
+        match option {
+            DownloadOption::Download => {
+                Some(Action::NextPage(Page::Download(DownloadPage::new(vec![
+                    selection,
+                ]))))
+            }
+            DownloadOption::ListUrls => {
+                Some(Action::NextPage(Page::UrlList(UrlList::new(selection))))
+            }
+            DownloadOption::Export(format) => {
+                self.status = Some(match export::export(format, &selection) {
+                    Ok(paths) => Status::info(format!(
+                        "Wrote {}",
+                        paths.iter().map(|p| p.display()).join(", ")
+                    )),
+                    Err(e) => Status::error(format!("Export failed: {e}")),
+                });
+                None
+            }
         }
     }
 
     pub fn draw(&mut self, frame: &mut ratatui::Frame, area: ratatui::layout::Rect) {
+        let area = Status::draw(self.status.as_ref(), frame, area);
         self.list.draw(frame, area);
     }
 
     pub fn keybinds(&self) -> Vec<KeyBind> {
-        self.list.keybinds(true)
+        let mut binds = self.list.keybinds(true);
+        if !self.list.is_searching() {
+            binds.extend(basket::keybinds(true));
+        }
+        binds
     }
 }
 
+#[derive(Clone, Copy)]
 enum DownloadOption {
     Download,
     ListUrls,
+    Export(ExportFormat),
 }
 
 impl AsRef<str> for DownloadOption {
@@ -73,21 +157,7 @@ impl AsRef<str> for DownloadOption {
         match self {
             DownloadOption::Download => "Download now",
             DownloadOption::ListUrls => "List URLs",
+            DownloadOption::Export(format) => format.description(),
         }
     }
 }
-
-fn sources_to_urls(sources: impl Iterator<Item = WebSource>) -> Vec<String> {
-    sources.into_iter().map(|s| s.url).collect()
-}
-
-fn extract_sources(config: &Config) -> impl Iterator<Item = WebSource> + use<'_> {
-    config
-        .iso
-        .iter()
-        .chain(config.img.iter())
-        .filter_map(|s| match s {
-            Source::Web(web) => Some(web.clone()),
-            _ => None,
-        })
-}
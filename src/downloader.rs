@@ -0,0 +1,473 @@
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use quickget_core::data_structures::WebSource;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Semaphore;
+
+use crate::{
+    backend::{self, Backend},
+    checksum::{ChecksumMismatch, ChecksumVerification},
+    filename,
+    network::NetworkError,
+};
+
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Minimum time between progress events sent by each download
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// What to do when a downloaded file already exists
+#[derive(Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OverwritePolicy {
+    /// Fail the download
+    #[default]
+    Never,
+    /// Replace the existing file
+    Overwrite,
+    /// Keep the existing file and skip the download
+    Skip,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum DownloadError {
+    #[error("{0}")]
+    Reqwest(#[from] reqwest::Error),
+    #[error("Timed out waiting for the server")]
+    Timeout,
+    #[error("{0}")]
+    Network(NetworkError),
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("{0}")]
+    Checksum(#[from] ChecksumMismatch),
+    #[error("Could not run {0}: {1}")]
+    Command(&'static str, std::io::Error),
+    #[error("{0} failed: {1}")]
+    External(&'static str, String),
+    #[error("{0} is a local file, which is only downloaded from a local catalogue")]
+    LocalFile(String),
+}
+
+/// Broad cause of a failed download
+#[derive(Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FailureKind {
+    Network,
+    Io,
+    Checksum,
+}
+
+impl DownloadError {
+    pub fn kind(&self) -> FailureKind {
+        match self {
+            DownloadError::Reqwest(_)
+            | DownloadError::Timeout
+            | DownloadError::Network(_)
+            | DownloadError::External(..) => FailureKind::Network,
+            DownloadError::Io(_) | DownloadError::Command(..) | DownloadError::LocalFile(_) => {
+                FailureKind::Io
+            }
+            DownloadError::Checksum(_) => FailureKind::Checksum,
+        }
+    }
+}
+
+/// Where a finished download was saved
+pub struct Saved {
+    pub path: PathBuf,
+    /// Whether an existing file was kept rather than downloaded
+    pub skipped: bool,
+}
+
+/// Something which happened during a download, passed to its callback
+pub enum Event<'a> {
+    /// The server responded, and the file is about to be written
+    Started {
+        name: &'a str,
+        path: &'a Path,
+        total: Option<u64>,
+    },
+    /// More of the file was written. Sent at most every 100ms
+    Progress {
+        bytes: u64,
+        total: Option<u64>,
+    },
+    /// A request failed and will be sent again
+    Retry {
+        attempt: u32,
+        error: &'a dyn std::error::Error,
+    },
+    /// The file was compared against its checksum
+    Checksum {
+        algorithm: &'static str,
+        matches: bool,
+    },
+    Completed {
+        bytes: u64,
+        skipped: bool,
+    },
+}
+
+/// State of a download, shared between the task running it and anything observing or controlling it
+#[derive(Default)]
+pub struct Transfer {
+    bytes: AtomicU64,
+    total: AtomicU64,
+    paused: AtomicBool,
+    /// The file being written, removed if the download is stopped
+    partial_file: Mutex<Option<PathBuf>>,
+}
+
+impl Transfer {
+    /// Bytes written so far
+    pub fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    /// Size of the file, or 0 if the server didn't give one
+    pub fn total(&self) -> u64 {
+        self.total.load(Ordering::Relaxed)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    pub fn toggle_pause(&self) {
+        self.paused.fetch_xor(true, Ordering::Relaxed);
+    }
+
+    /// Remove the unfinished file of a download which has been stopped
+    pub fn remove_partial_file(&self) {
+        if let Some(path) = self.partial_file.lock().unwrap().take() {
+            _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// Downloads files, sharing limits on concurrency and speed between them,
+/// and making sure no two downloads are saved to the same path
+#[derive(Clone)]
+pub struct Downloader {
+    backend: Arc<dyn Backend>,
+    overwrite: OverwritePolicy,
+    permits: Option<Arc<Semaphore>>,
+    rate: Option<Arc<RateLimiter>>,
+    /// Whether `file://` URLs are copied, which lets whoever wrote the URLs read any of the user's files
+    local_files: bool,
+    /// Paths which downloads have been started at
+    claimed: Arc<Mutex<HashSet<PathBuf>>>,
+}
+
+impl Downloader {
+    /// `concurrency` limits the number of simultaneous downloads, and `rate_limit` their combined bytes per second
+    pub fn new(
+        backend: Arc<dyn Backend>,
+        overwrite: OverwritePolicy,
+        concurrency: Option<usize>,
+        rate_limit: Option<u64>,
+    ) -> Self {
+        Self {
+            backend,
+            overwrite,
+            permits: concurrency.map(|n| Arc::new(Semaphore::new(n))),
+            rate: rate_limit.map(|rate| Arc::new(RateLimiter::new(rate))),
+            local_files: false,
+            claimed: Arc::default(),
+        }
+    }
+
+    /// Copy `file://` URLs, which should only be allowed when they come from a trusted source such as a local catalogue
+    pub fn with_local_files(mut self, local_files: bool) -> Self {
+        self.local_files = local_files;
+        self
+    }
+
+    /// Whether a download from a URL can be paused, depending on the backend used for it
+    pub fn can_pause(&self, url: &str) -> bool {
+        backend::for_url(url, &self.backend, self.local_files).is_ok_and(|b| b.can_pause())
+    }
+
+    /// Download a file with the default backend, or directly if it's local and local files are allowed
+    pub async fn download(
+        &self,
+        source: &WebSource,
+        path_for: impl FnOnce(&str) -> PathBuf + Send,
+        transfer: &Transfer,
+        on_event: impl FnMut(Event<'_>) + Send,
+    ) -> Result<Saved, DownloadError> {
+        let backend = backend::for_url(&source.url, &self.backend, self.local_files)?;
+        self.download_with(backend.as_ref(), source, path_for, transfer, on_event)
+            .await
+    }
+
+    /// Download a file using a specific backend, verifying its checksum if it has one.
+    /// `path_for` is given the name of the file, which may depend on redirects, and returns where to save it
+    pub async fn download_with(
+        &self,
+        backend: &dyn Backend,
+        source: &WebSource,
+        path_for: impl FnOnce(&str) -> PathBuf + Send,
+        transfer: &Transfer,
+        mut on_event: impl FnMut(Event<'_>) + Send,
+    ) -> Result<Saved, DownloadError> {
+        let _permit = match &self.permits {
+            Some(permits) => Some(permits.acquire().await.expect("semaphore is never closed")),
+            None => None,
+        };
+        let mut sink = Sink {
+            source,
+            path_for: Some(Box::new(path_for)),
+            overwrite: self.overwrite,
+            rate: self.rate.as_deref(),
+            claimed: &self.claimed,
+            transfer,
+            on_event: &mut on_event,
+            verification: source.checksum.clone().and_then(ChecksumVerification::new),
+            file: None,
+            path: None,
+            total: None,
+            streamed: false,
+            last_progress: Instant::now(),
+        };
+        if let Err(e) = backend.fetch(&source.url, &mut sink).await {
+            // Unlike a partly streamed file, nothing is known about what a failed program left behind
+            if !sink.streamed {
+                transfer.remove_partial_file();
+            }
+            // Failed downloads may be tried again at the same path
+            if let Some(path) = &sink.path {
+                self.claimed.lock().unwrap().remove(path);
+            }
+            return Err(e);
+        }
+        sink.finish().await
+    }
+}
+
+type PathFor<'a> = Box<dyn FnOnce(&str) -> PathBuf + Send + 'a>;
+
+/// Where a backend delivers a file. Backends call [`Sink::start`] once they know the file's name,
+/// then either stream it with [`Sink::write`], or write it themselves and report [`Sink::progress`]
+pub struct Sink<'a> {
+    source: &'a WebSource,
+    path_for: Option<PathFor<'a>>,
+    overwrite: OverwritePolicy,
+    rate: Option<&'a RateLimiter>,
+    claimed: &'a Mutex<HashSet<PathBuf>>,
+    transfer: &'a Transfer,
+    on_event: &'a mut (dyn FnMut(Event<'_>) + Send),
+    verification: Option<ChecksumVerification>,
+    file: Option<File>,
+    path: Option<PathBuf>,
+    total: Option<u64>,
+    /// Whether the file was written through the sink, and so has already been hashed
+    streamed: bool,
+    last_progress: Instant,
+}
+
+impl Sink<'_> {
+    /// Create the file, given any name the server gave it, which is only used if the source doesn't name it.
+    /// Returns where to write it, or `None` if an existing file is kept and nothing should be written
+    pub fn start(
+        &mut self,
+        file_name: Option<&str>,
+        total: Option<u64>,
+    ) -> Result<Option<PathBuf>, DownloadError> {
+        let name = filename::resolve(
+            self.source.file_name.as_deref(),
+            file_name,
+            &self.source.url,
+        );
+        let path_for = self
+            .path_for
+            .take()
+            .expect("downloads are only started once");
+        let path = {
+            let mut claimed = self.claimed.lock().unwrap();
+            let path = filename::deduplicate(&path_for(&name), |p| claimed.contains(p));
+            claimed.insert(path.clone());
+            path
+        };
+        self.path = Some(path.clone());
+        self.set_total(total);
+        (self.on_event)(Event::Started {
+            name: &name,
+            path: &path,
+            total,
+        });
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = match self.overwrite {
+            OverwritePolicy::Never => File::create_new(&path)?,
+            OverwritePolicy::Overwrite => File::create(&path)?,
+            OverwritePolicy::Skip if path.exists() => {
+                let size = fs::metadata(&path)?.len();
+                self.transfer.total.store(size, Ordering::Relaxed);
+                self.transfer.bytes.store(size, Ordering::Relaxed);
+                return Ok(None);
+            }
+            OverwritePolicy::Skip => File::create_new(&path)?,
+        };
+        *self.transfer.partial_file.lock().unwrap() = Some(path.clone());
+        self.file = Some(file);
+        Ok(Some(path))
+    }
+
+    /// Write the next part of the file, waiting while the download is paused or over the rate limit
+    pub async fn write(&mut self, chunk: &[u8]) -> Result<(), DownloadError> {
+        while self.transfer.is_paused() {
+            tokio::time::sleep(PAUSE_POLL_INTERVAL).await;
+        }
+        if let Some(rate) = self.rate {
+            rate.consume(chunk.len() as u64).await;
+        }
+        let file = self
+            .file
+            .as_mut()
+            .expect("downloads are started before writing");
+        file.write_all(chunk)?;
+        self.streamed = true;
+        if let Some(verification) = self.verification.as_mut() {
+            verification.write_chunk(chunk);
+        }
+        let bytes = self
+            .transfer
+            .bytes
+            .fetch_add(chunk.len() as u64, Ordering::Relaxed)
+            + chunk.len() as u64;
+        self.send_progress(bytes);
+        Ok(())
+    }
+
+    /// Report the progress of a backend writing the file itself
+    pub fn progress(&mut self, bytes: u64, total: Option<u64>) {
+        if total.is_some() {
+            self.set_total(total);
+        }
+        self.transfer.bytes.store(bytes, Ordering::Relaxed);
+        self.send_progress(bytes);
+    }
+
+    /// Report that a request failed and is about to be sent again
+    pub fn retry(&mut self, attempt: u32, error: &dyn std::error::Error) {
+        (self.on_event)(Event::Retry { attempt, error });
+    }
+
+    fn set_total(&mut self, total: Option<u64>) {
+        self.total = total;
+        self.transfer
+            .total
+            .store(total.unwrap_or(0), Ordering::Relaxed);
+    }
+
+    fn send_progress(&mut self, bytes: u64) {
+        if self.last_progress.elapsed() >= PROGRESS_INTERVAL {
+            self.last_progress = Instant::now();
+            (self.on_event)(Event::Progress {
+                bytes,
+                total: self.total,
+            });
+        }
+    }
+
+    /// Verify the finished file
+    async fn finish(mut self) -> Result<Saved, DownloadError> {
+        let path = self
+            .path
+            .take()
+            .expect("backends start downloads which succeed");
+        let Some(file) = self.file.take() else {
+            (self.on_event)(Event::Completed {
+                bytes: self.transfer.bytes(),
+                skipped: true,
+            });
+            return Ok(Saved {
+                path,
+                skipped: true,
+            });
+        };
+        drop(file);
+        if !self.streamed {
+            self.transfer
+                .bytes
+                .store(fs::metadata(&path)?.len(), Ordering::Relaxed);
+        }
+
+        if let Some(mut verification) = self.verification.take() {
+            if !self.streamed {
+                // Hashing a large file takes a while, so it's kept off the async threads
+                let file_path = path.clone();
+                verification = tokio::task::spawn_blocking(move || {
+                    verification.write_file(&file_path).map(|_| verification)
+                })
+                .await
+                .expect("hashing doesn't panic")?;
+            }
+            let algorithm = verification.algorithm();
+            let result = verification.validate();
+            (self.on_event)(Event::Checksum {
+                algorithm,
+                matches: result.is_ok(),
+            });
+            result?;
+        }
+        (self.on_event)(Event::Completed {
+            bytes: self.transfer.bytes(),
+            skipped: false,
+        });
+        Ok(Saved {
+            path,
+            skipped: false,
+        })
+    }
+}
+
+/// Token bucket limiting the combined speed of downloads, allowing bursts of up to a second
+struct RateLimiter {
+    bytes_per_second: f64,
+    // Time of the last update, and the available bytes, which become negative when overdrawn
+    state: Mutex<(Instant, f64)>,
+}
+
+impl RateLimiter {
+    fn new(bytes_per_second: u64) -> Self {
+        let bytes_per_second = bytes_per_second as f64;
+        Self {
+            bytes_per_second,
+            state: Mutex::new((Instant::now(), bytes_per_second)),
+        }
+    }
+
+    async fn consume(&self, bytes: u64) {
+        let wait = {
+            let mut state = self.state.lock().unwrap();
+            let (last_update, available) = &mut *state;
+            let now = Instant::now();
+            let refill = now.duration_since(*last_update).as_secs_f64() * self.bytes_per_second;
+            *available = (*available + refill).min(self.bytes_per_second) - bytes as f64;
+            *last_update = now;
+            Duration::from_secs_f64((-*available / self.bytes_per_second).max(0.0))
+        };
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// Name of the file a source is expected to download to.
+/// Only used for display; the final filename may depend on the server
+pub fn display_name(source: &WebSource) -> String {
+    filename::resolve(source.file_name.as_deref(), None, &source.url)
+}
@@ -1,52 +1,118 @@
 use quickget_core::data_structures::Config;
+use quickosdl::catalogue::OSInfo;
 use ratatui::{
-    crossterm::event::{KeyCode, KeyEvent},
-    text::Span,
+    crossterm::event::{KeyEvent, MouseEvent},
+    text::{Line, Span},
     widgets::ListItem,
 };
 
 use crate::{
     app::{Action, Page},
+    basket::{self, BasketPage},
     download_options::DownloadOptions,
     keybinds::KeyBind,
-    searchable_list::{SearchableItem, SearchableList},
+    keymap::{keymap, Command},
+    searchable_list::{Highlights, SearchableItem, SearchableList},
 };
 
-impl SearchableItem for Config {
-    fn to_list_item(&self, _: usize) -> ListItem<'_> {
-        ListItem::from(Span::raw(self.edition.as_deref().unwrap_or("None")))
+struct Edition {
+    config: Config,
+    in_basket: bool,
+}
+
+impl SearchableItem for Edition {
+    fn to_list_item(&self, _: usize, highlights: Highlights) -> ListItem<'_> {
+        let edition = self.config.edition.as_deref().unwrap_or("None");
+        let mut line = vec![];
+        if self.in_basket {
+            line.push(Span::raw("[+] "));
+        }
+        line.extend(highlights.spans(0, edition, 0));
+        ListItem::from(Line::from(line))
     }
-    fn to_filter(&self) -> std::borrow::Cow<'_, str> {
-        self.edition.as_deref().unwrap_or("None").into()
+    fn filter_fields(&self) -> Vec<std::borrow::Cow<'_, str>> {
+        vec![self.config.edition.as_deref().unwrap_or("None").into()]
     }
 }
 
 pub struct EditionSelection {
-    list: SearchableList<Config>,
+    os: OSInfo,
+    release: String,
+    list: SearchableList<Edition>,
 }
 
 impl EditionSelection {
-    pub fn new(list: Vec<Config>) -> Self {
+    pub fn new(os: OSInfo, list: Vec<Config>) -> Self {
+        let release = list.first().map(|c| c.release.clone()).unwrap_or_default();
+        let editions: Vec<Edition> = list
+            .into_iter()
+            .map(|config| Edition {
+                config,
+                in_basket: false,
+            })
+            .collect();
         Self {
-            list: SearchableList::new(list),
+            os,
+            release,
+            list: SearchableList::new(editions),
         }
     }
 
     pub fn handle_key(&mut self, key: &KeyEvent) -> Option<Action> {
-        match key.code {
-            KeyCode::Char('q') if !self.list.is_searching() => Some(Action::Exit),
-            KeyCode::Char('h') if !self.list.is_searching() => Some(Action::PrevPage),
-            _ => self.list.handle_key(key).map(|config| {
-                Action::NextPage(Page::DownloadOptions(DownloadOptions::new(config.clone())))
-            }),
+        match keymap()
+            .command(key)
+            .filter(|_| self.list.accepts_commands())
+        {
+            Some(Command::Quit) => Some(Action::Exit),
+            Some(Command::Back) => Some(Action::PrevPage),
+            Some(Command::ToggleBasket) => {
+                if let Some(edition) = self.list.selected_item() {
+                    basket::toggle(&self.os, &edition.config);
+                }
+                None
+            }
+            Some(Command::ViewBasket) if basket::len() > 0 => {
+                Some(Action::NextPage(Page::Basket(BasketPage::new())))
+            }
+            _ => self
+                .list
+                .handle_key(key)
+                .map(|edition| open_edition(&self.os, edition)),
         }
     }
 
+    pub fn selection(&self) -> Vec<String> {
+        vec![self.os.pretty_name.clone(), self.release.clone()]
+    }
+
+    pub fn accepts_commands(&self) -> bool {
+        self.list.accepts_commands()
+    }
+
+    pub fn handle_mouse(&mut self, mouse: &MouseEvent) -> Option<Action> {
+        let edition = self.list.handle_mouse(mouse)?;
+        Some(open_edition(&self.os, edition))
+    }
+
     pub fn draw(&mut self, frame: &mut ratatui::Frame, area: ratatui::layout::Rect) {
+        for edition in self.list.items_mut() {
+            edition.in_basket = basket::contains(&self.os, &edition.config);
+        }
         self.list.draw(frame, area);
     }
 
     pub fn keybinds(&self) -> Vec<KeyBind> {
-        self.list.keybinds(true)
+        let mut binds = self.list.keybinds(true);
+        if !self.list.is_searching() {
+            binds.extend(basket::keybinds(true));
+        }
+        binds
     }
 }
+
+fn open_edition(os: &OSInfo, edition: &Edition) -> Action {
+    Action::NextPage(Page::DownloadOptions(DownloadOptions::new(
+        os.clone(),
+        edition.config.clone(),
+    )))
+}
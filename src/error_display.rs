@@ -1,13 +1,13 @@
 use ratatui::{
-    crossterm::event::{KeyCode, KeyEvent},
+    crossterm::event::KeyEvent,
     layout::{Constraint, Flex, Layout, Rect},
-    style::{Color, Style, Stylize},
+    style::{Style, Stylize},
     text::{Line, Span},
     widgets::Paragraph,
     Frame,
 };
 
-use crate::{app::Action, keybinds::KeyBind};
+use crate::{app::Action, keybinds::KeyBind, theme::theme};
 
 pub struct ErrorDisplay {
     errors: Vec<String>,
@@ -17,21 +17,15 @@ impl ErrorDisplay {
     pub fn new(errors: Vec<String>) -> Self {
         Self { errors }
     }
-    pub fn handle_key(&mut self, key: &KeyEvent) -> Option<Action> {
-        match key.code {
-            KeyCode::Null => None,
-            _ => Some(Action::Exit),
-        }
+    pub fn handle_key(&mut self, _: &KeyEvent) -> Option<Action> {
+        Some(Action::Exit)
     }
     pub fn draw(&mut self, frame: &mut Frame, area: Rect) {
         let [centered] = Layout::vertical([Constraint::Length(self.errors.len() as u16 + 3)])
             .flex(Flex::Center)
             .areas(area);
         let mut error_lines = Vec::with_capacity(self.errors.len() + 3);
-        error_lines.push(Line::from(vec![Span::styled(
-            "Error:",
-            Style::default().bold().fg(Color::Red),
-        )]));
+        error_lines.push(Line::from(vec![Span::styled("Error:", theme().error)]));
 
         error_lines.extend(
             self.errors
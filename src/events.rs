@@ -0,0 +1,32 @@
+use std::sync::{
+    mpsc::{self, Receiver, Sender},
+    OnceLock,
+};
+
+use ratatui::crossterm::event::Event;
+
+static SENDER: OnceLock<Sender<AppEvent>> = OnceLock::new();
+
+/// Messages which wake the UI loop. Anything other than terminal input tells the current page
+/// that background work has made progress, so it should update and be redrawn
+pub enum AppEvent {
+    Terminal(Event),
+    CatalogueLoaded,
+    DownloadProgress,
+    DownloadFinished,
+    SizeFetched,
+    VerificationFinished,
+}
+
+/// Create the channel events are sent through. Events sent before this are dropped
+pub fn channel() -> Receiver<AppEvent> {
+    let (sender, receiver) = mpsc::channel();
+    _ = SENDER.set(sender);
+    receiver
+}
+
+pub fn send(event: AppEvent) {
+    if let Some(sender) = SENDER.get() {
+        _ = sender.send(event);
+    }
+}
@@ -0,0 +1,294 @@
+use std::{
+    fmt::Write,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use itertools::Itertools;
+use quickosdl::{catalogue::Selection, checksum::algorithm_name, downloader::display_name};
+
+use crate::{basket, settings::settings};
+
+/// Formats an image's sources can be exported to, for downloading them on another machine
+#[derive(Clone, Copy)]
+pub enum ExportFormat {
+    Aria2,
+    Metalink,
+    CurlScript,
+    WgetScript,
+    Checksums,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ExportError {
+    #[error("None of the files have a checksum")]
+    NoChecksums,
+    #[error("Could not write {0}: {1}")]
+    Io(PathBuf, io::Error),
+}
+
+impl ExportFormat {
+    pub fn description(&self) -> &'static str {
+        match self {
+            ExportFormat::Aria2 => "Export aria2 input file",
+            ExportFormat::Metalink => "Export Metalink",
+            ExportFormat::CurlScript => "Export curl script",
+            ExportFormat::WgetScript => "Export wget script",
+            ExportFormat::Checksums => "Export checksums",
+        }
+    }
+}
+
+/// A source as it appears in an export
+struct ExportedFile {
+    url: String,
+    /// Path relative to the export, following the filename template
+    name: String,
+    checksum: Option<Checksum>,
+    size: Option<u64>,
+}
+
+struct Checksum {
+    /// Algorithm name as used by aria2 and Metalink, e.g. `sha-256`
+    algorithm: String,
+    value: String,
+}
+
+impl Checksum {
+    /// The coreutils program which verifies this checksum, e.g. `sha256sum`
+    fn program(&self) -> String {
+        format!("{}sum", self.algorithm.replace('-', ""))
+    }
+
+    /// Conventional name of a manifest of these checksums, e.g. `SHA256SUMS`
+    fn manifest_name(&self) -> String {
+        format!("{}SUMS", self.algorithm.replace('-', "").to_uppercase())
+    }
+}
+
+/// Write the selection's sources in the given format to the output directory, returning the paths written.
+/// Paths within the exports are relative to it, so they can be used on another machine
+pub fn export(format: ExportFormat, selection: &Selection) -> Result<Vec<PathBuf>, ExportError> {
+    let settings = settings();
+    let files: Vec<ExportedFile> = selection
+        .sources()
+        .map(|source| ExportedFile {
+            name: settings.file_name(&display_name(&source), selection),
+            size: basket::remote_size(&source.url).flatten(),
+            checksum: source.checksum.as_deref().and_then(|value| {
+                Some(Checksum {
+                    algorithm: algorithm_name(value)?.to_lowercase(),
+                    value: value.to_lowercase(),
+                })
+            }),
+            url: source.url,
+        })
+        .collect();
+
+    let dir = settings.output_dir.clone().unwrap_or_default();
+    let base_name = base_name(selection);
+    let outputs = match format {
+        ExportFormat::Aria2 => vec![(format!("{base_name}.aria2"), aria2(&files))],
+        ExportFormat::Metalink => vec![(format!("{base_name}.meta4"), metalink(&files))],
+        ExportFormat::CurlScript => vec![(
+            format!("{base_name}.curl.sh"),
+            script(&files, selection, |f| {
+                format!(
+                    "curl --fail --location --continue-at - --output {} {}",
+                    quote(&f.name),
+                    quote(&f.url)
+                )
+            }),
+        )],
+        ExportFormat::WgetScript => vec![(
+            format!("{base_name}.wget.sh"),
+            script(&files, selection, |f| {
+                format!(
+                    "wget --continue --output-document={} {}",
+                    quote(&f.name),
+                    quote(&f.url)
+                )
+            }),
+        )],
+        ExportFormat::Checksums => {
+            let manifests = checksum_manifests(&files);
+            if manifests.is_empty() {
+                return Err(ExportError::NoChecksums);
+            }
+            manifests
+                .into_iter()
+                .map(|(name, contents)| (format!("{base_name}.{name}"), contents))
+                .collect()
+        }
+    };
+
+    if !dir.as_os_str().is_empty() {
+        fs::create_dir_all(&dir).map_err(|e| ExportError::Io(dir.clone(), e))?;
+    }
+    outputs
+        .into_iter()
+        .map(|(name, contents)| {
+            let path = dir.join(name);
+            fs::write(&path, contents).map_err(|e| ExportError::Io(path.clone(), e))?;
+            if matches!(format, ExportFormat::CurlScript | ExportFormat::WgetScript) {
+                make_executable(&path).map_err(|e| ExportError::Io(path.clone(), e))?;
+            }
+            Ok(path)
+        })
+        .collect()
+}
+
+/// Name shared by the files exported for a selection, e.g. `ubuntu-24.04-desktop-x86_64`
+fn base_name(selection: &Selection) -> String {
+    let config = &selection.config;
+    let arch = config.arch.to_string();
+    let name = [&selection.os.name, &config.release]
+        .into_iter()
+        .chain(config.edition.as_ref())
+        .chain([&arch])
+        .join("-");
+    name.chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '_' | '-' => c,
+            _ => '-',
+        })
+        .collect()
+}
+
+/// An aria2 input file, see https://aria2.github.io/manual/en/html/aria2c.html#input-file
+fn aria2(files: &[ExportedFile]) -> String {
+    let mut output = String::new();
+    for file in files {
+        let path = Path::new(&file.name);
+        _ = writeln!(output, "{}", file.url);
+        if let Some(dir) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            _ = writeln!(output, "  dir={}", dir.display());
+        }
+        if let Some(name) = path.file_name() {
+            _ = writeln!(output, "  out={}", name.to_string_lossy());
+        }
+        if let Some(checksum) = &file.checksum {
+            _ = writeln!(
+                output,
+                "  checksum={}={}",
+                checksum.algorithm, checksum.value
+            );
+        }
+    }
+    output
+}
+
+/// A Metalink 4 document, see RFC 5854
+fn metalink(files: &[ExportedFile]) -> String {
+    let mut output = String::from(concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<metalink xmlns=\"urn:ietf:params:xml:ns:metalink\">\n",
+        "  <generator>quickosdl/",
+        env!("CARGO_PKG_VERSION"),
+        "</generator>\n",
+    ));
+    for file in files {
+        _ = writeln!(output, "  <file name=\"{}\">", escape_xml(&file.name));
+        if let Some(size) = file.size {
+            _ = writeln!(output, "    <size>{size}</size>");
+        }
+        if let Some(checksum) = &file.checksum {
+            _ = writeln!(
+                output,
+                "    <hash type=\"{}\">{}</hash>",
+                checksum.algorithm, checksum.value
+            );
+        }
+        _ = writeln!(output, "    <url>{}</url>", escape_xml(&file.url));
+        _ = writeln!(output, "  </file>");
+    }
+    output.push_str("</metalink>\n");
+    output
+}
+
+/// A shell script which downloads each file with the given command, then verifies their checksums
+fn script(
+    files: &[ExportedFile],
+    selection: &Selection,
+    download: impl Fn(&ExportedFile) -> String,
+) -> String {
+    let config = &selection.config;
+    let mut output = String::from("#!/bin/sh\n");
+    _ = writeln!(
+        output,
+        "# {} {}{} ({}), exported by quickosdl",
+        selection.os.pretty_name,
+        config.release,
+        config
+            .edition
+            .as_ref()
+            .map(|e| format!(" {e}"))
+            .unwrap_or_default(),
+        config.arch
+    );
+    output.push_str("set -eu\ncd \"$(dirname \"$0\")\"\n\n");
+
+    let dirs = files
+        .iter()
+        .filter_map(|f| Path::new(&f.name).parent())
+        .filter(|p| !p.as_os_str().is_empty())
+        .unique();
+    for dir in dirs {
+        _ = writeln!(output, "mkdir -p {}", quote(&dir.to_string_lossy()));
+    }
+    for file in files {
+        _ = writeln!(output, "{}", download(file));
+    }
+
+    let checked = files.iter().filter_map(|f| Some((f.checksum.as_ref()?, f)));
+    for (program, files) in &checked.chunk_by(|(c, _)| c.program()) {
+        _ = writeln!(output, "\n{program} -c <<'EOF'");
+        for (checksum, file) in files {
+            _ = writeln!(output, "{}  {}", checksum.value, file.name);
+        }
+        output.push_str("EOF\n");
+    }
+    output
+}
+
+/// SHA256SUMS-style manifests, one for each algorithm used
+fn checksum_manifests(files: &[ExportedFile]) -> Vec<(String, String)> {
+    files
+        .iter()
+        .filter_map(|f| Some((f.checksum.as_ref()?, f)))
+        .into_group_map_by(|(c, _)| c.manifest_name())
+        .into_iter()
+        .sorted_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(name, files)| {
+            let contents = files
+                .iter()
+                .map(|(checksum, file)| format!("{}  {}\n", checksum.value, file.name))
+                .collect();
+            (name, contents)
+        })
+        .collect()
+}
+
+/// Quote a string for a POSIX shell
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+}
+
+#[cfg(not(unix))]
+fn make_executable(_: &Path) -> io::Result<()> {
+    Ok(())
+}
@@ -0,0 +1,355 @@
+use std::path::{Path, PathBuf};
+
+use reqwest::Url;
+
+/// Name used when neither the source, the server nor the URL name the file
+const GENERATED_NAME: &str = "download";
+/// Longest file name, in bytes, which common filesystems accept
+const MAX_LENGTH: usize = 255;
+/// Compression extensions, which are kept together with the extension before them
+const COMPRESSION_EXTENSIONS: [&str; 6] = ["gz", "xz", "bz2", "zst", "lz4", "7z"];
+/// Names Windows reserves for devices, with or without an extension
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// The name to save a file under, from the first of these which gives a usable name: the catalogue's name,
+/// the name given by the server through `Content-Disposition` or a redirect, the URL, or a generated name
+pub fn resolve(data: Option<&str>, server: Option<&str>, url: &str) -> String {
+    data.and_then(sanitize)
+        .or_else(|| server.and_then(sanitize))
+        .or_else(|| from_url(url))
+        .unwrap_or_else(|| GENERATED_NAME.to_string())
+}
+
+/// The file name given by a `Content-Disposition` header, preferring the RFC 6266 `filename*` parameter
+pub fn from_content_disposition(header: &str) -> Option<String> {
+    let params: Vec<(String, &str)> = parameters(header)
+        .into_iter()
+        .skip(1)
+        .filter_map(|param| param.split_once('='))
+        .map(|(key, value)| (key.trim().to_ascii_lowercase(), value.trim()))
+        .collect();
+    let extended = params
+        .iter()
+        .find(|(key, _)| key == "filename*")
+        .and_then(|(_, value)| {
+            // e.g. UTF-8''na%C3%AFve.iso, where the language between the quotes is optional
+            let (charset, rest) = value.split_once('\'')?;
+            let (_, encoded) = rest.split_once('\'')?;
+            let decoded = percent_decode(encoded);
+            match charset.to_ascii_lowercase().as_str() {
+                "utf-8" => String::from_utf8(decoded).ok(),
+                "iso-8859-1" => Some(decoded.into_iter().map(char::from).collect()),
+                _ => None,
+            }
+        });
+    let plain = || {
+        let (_, value) = params.iter().find(|(key, _)| key == "filename")?;
+        let value = match value.strip_prefix('"') {
+            Some(quoted) => unquote(quoted.strip_suffix('"').unwrap_or(quoted)),
+            None => value.to_string(),
+        };
+        Some(value)
+    };
+    extended
+        .and_then(|name| sanitize(&name))
+        .or_else(|| plain().and_then(|name| sanitize(&name)))
+}
+
+/// Split a header into its value and parameters, on semicolons which aren't within quotes
+fn parameters(header: &str) -> Vec<&str> {
+    let mut params = vec![];
+    let (mut start, mut quoted, mut escaped) = (0, false, false);
+    for (i, c) in header.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ';' if !quoted => {
+                params.push(&header[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    params.push(&header[start..]);
+    params
+}
+
+/// The contents of a quoted string, where a backslash escapes the character after it
+fn unquote(quoted: &str) -> String {
+    let mut value = String::with_capacity(quoted.len());
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => value.extend(chars.next()),
+            c => value.push(c),
+        }
+    }
+    value
+}
+
+/// The file name at the end of a URL's path
+pub fn from_url(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    let segment = url.path_segments()?.rev().find(|s| !s.is_empty())?;
+    let name = String::from_utf8_lossy(&percent_decode(segment)).into_owned();
+    sanitize(&name)
+}
+
+/// Make a name safe to use as a file name within the output directory: only its last path component is kept,
+/// characters which are illegal on common filesystems are replaced, and it can't be hidden or reserved.
+/// Returns `None` if nothing usable is left
+pub fn sanitize(name: &str) -> Option<String> {
+    let name = name.rsplit(['/', '\\']).next().unwrap_or(name);
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    // Leading dots would hide the file or leave `..`, and Windows drops trailing dots and spaces
+    let name = name
+        .trim_start_matches(|c: char| c == '.' || c.is_whitespace())
+        .trim_end_matches(|c: char| c == '.' || c.is_whitespace());
+    if name.is_empty() || name.chars().all(|c| c == '_') {
+        return None;
+    }
+    let stem = name.split('.').next().unwrap_or(name);
+    let name = if RESERVED_NAMES.iter().any(|r| r.eq_ignore_ascii_case(stem)) {
+        format!("_{name}")
+    } else {
+        name.to_string()
+    };
+    Some(truncate(&name))
+}
+
+/// Shorten a name to the maximum length, keeping its extension unless that's too long to leave room for the rest
+fn truncate(name: &str) -> String {
+    if name.len() <= MAX_LENGTH {
+        return name.to_string();
+    }
+    let (stem, extension) = match split_extension(name) {
+        (stem, extension) if extension.len() <= MAX_LENGTH / 2 => (stem, extension),
+        _ => (name, ""),
+    };
+    format!("{}{extension}", shorten(stem, MAX_LENGTH - extension.len()))
+}
+
+/// The longest start of a string which fits in `max` bytes
+fn shorten(text: &str, max: usize) -> &str {
+    let mut end = max.min(text.len());
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+/// A path which isn't `taken`, numbering it as `name-2.iso`, `name-3.iso` and so on if necessary.
+/// Names are shortened to make room for the number, as with an extension too long to keep
+pub fn deduplicate(path: &Path, taken: impl Fn(&Path) -> bool) -> PathBuf {
+    if !taken(path) {
+        return path.to_path_buf();
+    }
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| GENERATED_NAME.to_string());
+    let (stem, extension) = split_extension(&name);
+    (2..)
+        .map(|n| {
+            let (stem, suffix) = match format!("-{n}{extension}") {
+                suffix if suffix.len() <= MAX_LENGTH / 2 => (stem, suffix),
+                _ => (name.as_str(), format!("-{n}")),
+            };
+            let stem = shorten(stem, MAX_LENGTH - suffix.len());
+            path.with_file_name(format!("{stem}{suffix}"))
+        })
+        .find(|path| !taken(path))
+        .expect("some number is always free")
+}
+
+/// Split a name before its extension, keeping compression extensions with the one before them, e.g. `.img.xz`
+fn split_extension(name: &str) -> (&str, &str) {
+    let Some(dot) = name.rfind('.').filter(|&i| i > 0) else {
+        return (name, "");
+    };
+    let start = match name[..dot].rfind('.').filter(|&i| i > 0) {
+        Some(inner) if COMPRESSION_EXTENSIONS.contains(&&name[dot + 1..]) => inner,
+        _ => dot,
+    };
+    name.split_at(start)
+}
+
+fn percent_decode(text: &str) -> Vec<u8> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    decoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn traversal_keeps_only_the_last_component() {
+        assert_eq!(sanitize("../../evil.iso").as_deref(), Some("evil.iso"));
+        assert_eq!(sanitize("..\\..\\evil.iso").as_deref(), Some("evil.iso"));
+        assert_eq!(sanitize("/etc/passwd").as_deref(), Some("passwd"));
+        assert_eq!(sanitize(".."), None);
+        assert_eq!(sanitize("../"), None);
+        assert_eq!(sanitize(".hidden.iso").as_deref(), Some("hidden.iso"));
+    }
+
+    #[test]
+    fn illegal_characters_are_replaced() {
+        assert_eq!(sanitize("a<b>:c.iso").as_deref(), Some("a_b__c.iso"));
+        assert_eq!(sanitize("name\n.iso").as_deref(), Some("name_.iso"));
+        assert_eq!(sanitize("???"), None);
+    }
+
+    #[test]
+    fn reserved_names_are_prefixed() {
+        assert_eq!(sanitize("CON").as_deref(), Some("_CON"));
+        assert_eq!(sanitize("nul.txt").as_deref(), Some("_nul.txt"));
+        assert_eq!(sanitize("com1.tar.gz").as_deref(), Some("_com1.tar.gz"));
+        assert_eq!(sanitize("console.iso").as_deref(), Some("console.iso"));
+    }
+
+    #[test]
+    fn extended_file_names_are_percent_decoded() {
+        let header =
+            "attachment; filename=\"fallback.iso\"; filename*=UTF-8''na%C3%AFve%20image.iso";
+        assert_eq!(
+            from_content_disposition(header).as_deref(),
+            Some("naïve image.iso")
+        );
+        let header = "attachment; filename*=iso-8859-1'en'caf%E9.iso";
+        assert_eq!(
+            from_content_disposition(header).as_deref(),
+            Some("café.iso")
+        );
+    }
+
+    #[test]
+    fn unusable_extended_names_fall_back_to_the_plain_name() {
+        let header = "attachment; filename*=UTF-8''..%2F..; filename=\"plain.iso\"";
+        assert_eq!(
+            from_content_disposition(header).as_deref(),
+            Some("plain.iso")
+        );
+    }
+
+    #[test]
+    fn quoted_names_may_contain_separators_and_escapes() {
+        let header = "attachment; filename=\"a;b.iso\"";
+        assert_eq!(from_content_disposition(header).as_deref(), Some("a;b.iso"));
+        let header = "attachment; filename=\"say \\\"hi\\\".iso\"; size=5";
+        assert_eq!(
+            from_content_disposition(header).as_deref(),
+            Some("say _hi_.iso")
+        );
+        let header = "attachment; filename=unquoted.iso; size=5";
+        assert_eq!(
+            from_content_disposition(header).as_deref(),
+            Some("unquoted.iso")
+        );
+    }
+
+    #[test]
+    fn names_come_from_the_last_path_segment() {
+        assert_eq!(
+            from_url("https://example.com/images/ubuntu.iso/").as_deref(),
+            Some("ubuntu.iso")
+        );
+        assert_eq!(
+            from_url("https://example.com/a%20b.iso?token=1").as_deref(),
+            Some("a b.iso")
+        );
+        assert_eq!(from_url("https://example.com/"), None);
+        assert_eq!(resolve(None, None, "https://example.com/"), GENERATED_NAME);
+    }
+
+    #[test]
+    fn names_are_resolved_in_order() {
+        let url = "https://example.com/url.iso";
+        assert_eq!(
+            resolve(Some("data.iso"), Some("server.iso"), url),
+            "data.iso"
+        );
+        assert_eq!(resolve(Some(".."), Some("server.iso"), url), "server.iso");
+        assert_eq!(resolve(None, None, url), "url.iso");
+    }
+
+    #[test]
+    fn long_names_are_truncated_keeping_the_extension() {
+        let name = sanitize(&format!("{}.img.xz", "a".repeat(300))).unwrap();
+        assert_eq!(name.len(), MAX_LENGTH);
+        assert!(name.ends_with("a.img.xz"));
+
+        let name = sanitize(&format!("{}.iso", "é".repeat(200))).unwrap();
+        assert!(name.len() <= MAX_LENGTH);
+        assert!(name.ends_with("é.iso"));
+    }
+
+    #[test]
+    fn long_extensions_are_truncated_with_the_name() {
+        let name = sanitize(&format!("image.{}", "x".repeat(300))).unwrap();
+        assert_eq!(name.len(), MAX_LENGTH);
+        assert!(name.starts_with("image.xxx"));
+    }
+
+    #[test]
+    fn duplicates_are_numbered_before_compound_extensions() {
+        let taken = [
+            PathBuf::from("out/ubuntu.img.xz"),
+            PathBuf::from("out/ubuntu-2.img.xz"),
+        ];
+        let path = deduplicate(Path::new("out/ubuntu.img.xz"), |p| {
+            taken.iter().any(|t| t == p)
+        });
+        assert_eq!(path, Path::new("out/ubuntu-3.img.xz"));
+        let path = deduplicate(Path::new("out/notes.v2.txt"), |p| {
+            p == Path::new("out/notes.v2.txt")
+        });
+        assert_eq!(path, Path::new("out/notes.v2-2.txt"));
+        let path = deduplicate(Path::new("out/free.iso"), |_| false);
+        assert_eq!(path, Path::new("out/free.iso"));
+    }
+
+    #[test]
+    fn duplicates_of_long_names_stay_within_the_limit() {
+        let name = sanitize(&format!("{}.img.xz", "é".repeat(200))).unwrap();
+        let path = deduplicate(Path::new(&name), |p| p == Path::new(&name));
+        let numbered = path.to_str().unwrap();
+        assert!(numbered.len() <= MAX_LENGTH);
+        assert!(numbered.ends_with("é-2.img.xz"));
+
+        let name = sanitize(&format!("image.{}", "x".repeat(300))).unwrap();
+        let path = deduplicate(Path::new(&name), |p| p == Path::new(&name));
+        let numbered = path.to_str().unwrap();
+        assert_eq!(numbered.len(), MAX_LENGTH);
+        assert!(numbered.starts_with("image.xxx") && numbered.ends_with("x-2"));
+    }
+}
@@ -0,0 +1,145 @@
+use std::borrow::Cow;
+
+const MATCH_SCORE: i64 = 16;
+const CONSECUTIVE_BONUS: i64 = 8;
+const BOUNDARY_BONUS: i64 = 8;
+const START_BONUS: i64 = 8;
+const GAP_START_PENALTY: i64 = 3;
+const GAP_EXTENSION_PENALTY: i64 = 1;
+// Matches in less important fields (e.g. descriptions) should rank below matches in names
+const FIELD_PENALTY: i64 = 4;
+
+pub struct FuzzyMatch {
+    pub score: i64,
+    /// Byte offsets of matched characters, one entry per field
+    pub highlights: Vec<Vec<usize>>,
+}
+
+/// Match a whitespace separated query against a list of fields, ordered by importance.
+/// Every term of the query must match at least one of the fields for the item to match.
+pub fn match_fields(query: &str, fields: &[Cow<'_, str>]) -> Option<FuzzyMatch> {
+    let mut score = 0;
+    let mut highlights = vec![Vec::new(); fields.len()];
+
+    for term in query.split_whitespace() {
+        let term: Vec<char> = term.chars().collect();
+        let (field_index, term_score, positions) = fields
+            .iter()
+            .enumerate()
+            .filter_map(|(index, field)| {
+                let (score, positions) = match_term(&term, field)?;
+                Some((index, score - index as i64 * FIELD_PENALTY, positions))
+            })
+            .max_by_key(|(index, score, _)| (*score, -(*index as i64)))?;
+        score += term_score;
+        highlights[field_index].extend(positions);
+    }
+
+    for field in highlights.iter_mut() {
+        field.sort_unstable();
+        field.dedup();
+    }
+    Some(FuzzyMatch { score, highlights })
+}
+
+/// Returns the score and matched byte offsets of the best match of a single term within text
+fn match_term(term: &[char], text: &str) -> Option<(i64, Vec<usize>)> {
+    if term.is_empty() {
+        return Some((0, vec![]));
+    }
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+
+    let substring = substring_match(term, &chars);
+    let subsequence = subsequence_match(term, &chars);
+
+    [substring, subsequence]
+        .into_iter()
+        .flatten()
+        .map(|positions| (score_positions(&chars, &positions), positions))
+        .max_by_key(|(score, _)| *score)
+        .map(|(score, positions)| (score, positions.iter().map(|&p| chars[p].0).collect()))
+}
+
+/// Finds a contiguous occurrence of the term, preferring one starting at a word boundary
+fn substring_match(term: &[char], chars: &[(usize, char)]) -> Option<Vec<usize>> {
+    let starts: Vec<usize> = (0..=chars.len().checked_sub(term.len())?)
+        .filter(|&start| {
+            term.iter()
+                .zip(&chars[start..])
+                .all(|(&t, &(_, c))| chars_eq(t, c))
+        })
+        .collect();
+    let start = starts
+        .iter()
+        .find(|&&start| is_boundary(chars, start))
+        .or(starts.first())?;
+    Some((*start..*start + term.len()).collect())
+}
+
+/// Finds the first subsequence match, then walks backwards from its end to tighten the window
+fn subsequence_match(term: &[char], chars: &[(usize, char)]) -> Option<Vec<usize>> {
+    let mut term_index = 0;
+    let mut end = None;
+    for (index, &(_, c)) in chars.iter().enumerate() {
+        if chars_eq(term[term_index], c) {
+            term_index += 1;
+            if term_index == term.len() {
+                end = Some(index);
+                break;
+            }
+        }
+    }
+    let end = end?;
+
+    let mut positions = Vec::with_capacity(term.len());
+    let mut term_chars = term.iter().rev().peekable();
+    for index in (0..=end).rev() {
+        match term_chars.peek() {
+            Some(&&t) if chars_eq(t, chars[index].1) => {
+                positions.push(index);
+                term_chars.next();
+            }
+            Some(_) => {}
+            None => break,
+        }
+    }
+    positions.reverse();
+    Some(positions)
+}
+
+fn score_positions(chars: &[(usize, char)], positions: &[usize]) -> i64 {
+    let mut score = 0;
+    let mut prev: Option<usize> = None;
+    for &pos in positions {
+        score += MATCH_SCORE;
+        if pos == 0 {
+            score += START_BONUS;
+        }
+        if is_boundary(chars, pos) {
+            score += BOUNDARY_BONUS;
+        }
+        match prev {
+            Some(prev) if pos == prev + 1 => score += CONSECUTIVE_BONUS,
+            Some(prev) => {
+                score -= GAP_START_PENALTY + (pos - prev - 2) as i64 * GAP_EXTENSION_PENALTY
+            }
+            None => {}
+        }
+        prev = Some(pos);
+    }
+    score
+}
+
+fn is_boundary(chars: &[(usize, char)], pos: usize) -> bool {
+    match pos.checked_sub(1).map(|p| chars[p].1) {
+        None => true,
+        Some(prev) => {
+            let curr = chars[pos].1;
+            !prev.is_alphanumeric() || (prev.is_lowercase() && curr.is_uppercase())
+        }
+    }
+}
+
+fn chars_eq(a: char, b: char) -> bool {
+    a == b || a.to_lowercase().eq(b.to_lowercase())
+}
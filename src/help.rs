@@ -0,0 +1,142 @@
+use ratatui::{
+    crossterm::event::{KeyCode, KeyEvent, MouseEvent, MouseEventKind},
+    layout::{Constraint, Flex, Layout, Rect},
+    style::{Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, Clear, Paragraph},
+    Frame,
+};
+
+use crate::{
+    keybinds::KeyBind,
+    keymap::{keymap, Command},
+};
+
+/// Popup describing the current page and listing all of its keybinds, along with the global ones
+pub struct Help {
+    page: String,
+    description: &'static str,
+    page_binds: Vec<KeyBind>,
+    global_binds: Vec<KeyBind>,
+    scroll: u16,
+    // Scrollable distance from the last draw
+    max_scroll: u16,
+    page_height: u16,
+}
+
+impl Help {
+    pub fn new(
+        page: String,
+        description: &'static str,
+        page_binds: Vec<KeyBind>,
+        global_binds: Vec<KeyBind>,
+    ) -> Self {
+        Self {
+            page,
+            description,
+            page_binds,
+            global_binds,
+            scroll: 0,
+            max_scroll: 0,
+            page_height: 0,
+        }
+    }
+
+    /// Returns whether the popup should stay open
+    pub fn handle_key(&mut self, key: &KeyEvent) -> bool {
+        match keymap().command(key) {
+            Some(Command::Help | Command::Quit | Command::Back) => return false,
+            Some(Command::Next) => self.scroll_by(1),
+            Some(Command::Prev) => self.scroll_by(-1),
+            Some(Command::PageDown) => self.scroll_by(self.page_height as i32),
+            Some(Command::PageUp) => self.scroll_by(-(self.page_height as i32)),
+            Some(Command::First) => self.scroll = 0,
+            Some(Command::Last) => self.scroll = self.max_scroll,
+            _ => return key.code != KeyCode::Esc,
+        }
+        true
+    }
+
+    /// Returns whether the popup should stay open. Clicking anywhere closes it
+    pub fn handle_mouse(&mut self, mouse: &MouseEvent) -> bool {
+        match mouse.kind {
+            MouseEventKind::ScrollDown => self.scroll_by(1),
+            MouseEventKind::ScrollUp => self.scroll_by(-1),
+            MouseEventKind::Down(_) => return false,
+            _ => {}
+        }
+        true
+    }
+
+    fn scroll_by(&mut self, delta: i32) {
+        self.scroll = (self.scroll as i32 + delta).clamp(0, self.max_scroll as i32) as u16;
+    }
+
+    pub fn draw(&mut self, frame: &mut Frame, area: Rect) {
+        let [popup] = Layout::horizontal([Constraint::Max(80)])
+            .flex(Flex::Center)
+            .areas(area);
+        let [popup] = Layout::vertical([Constraint::Percentage(80)])
+            .flex(Flex::Center)
+            .areas(popup);
+        let block = Block::bordered().title(format!(" Help: {} ", self.page));
+        let inner = block.inner(popup);
+
+        let mut lines: Vec<Line> = wrap(self.description, inner.width as usize)
+            .into_iter()
+            .map(Line::from)
+            .collect();
+        lines.push(Line::default());
+        lines.extend(keybind_lines("This page", &self.page_binds));
+        lines.push(Line::default());
+        lines.extend(keybind_lines("Everywhere", &self.global_binds));
+
+        self.page_height = inner.height;
+        self.max_scroll = (lines.len() as u16).saturating_sub(inner.height);
+        self.scroll = self.scroll.min(self.max_scroll);
+
+        frame.render_widget(Clear, popup);
+        frame.render_widget(
+            Paragraph::new(lines).scroll((self.scroll, 0)).block(block),
+            popup,
+        );
+    }
+}
+
+fn keybind_lines(heading: &'static str, binds: &[KeyBind]) -> Vec<Line<'static>> {
+    let key_width = binds
+        .iter()
+        .map(|b| b.keys().chars().count())
+        .max()
+        .unwrap_or_default();
+    let mut lines = vec![Line::styled(heading, Style::new().bold().underlined())];
+    lines.extend(binds.iter().map(|bind| {
+        let keys = bind.keys();
+        let padding = " ".repeat(key_width - keys.chars().count() + 2);
+        Line::from(vec![
+            Span::styled(keys, Style::new().bold()),
+            Span::raw(padding),
+            Span::raw(bind.action()),
+        ])
+    }));
+    lines
+}
+
+/// Split text into lines of at most `width` characters, breaking between words
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = vec![];
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
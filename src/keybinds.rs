@@ -1,3 +1,5 @@
+use std::borrow::Cow;
+
 use ratatui::{
     layout::Rect,
     style::{Style, Stylize},
@@ -7,20 +9,35 @@ use ratatui::{
 };
 
 pub struct KeyBind {
-    keys: Vec<&'static str>,
+    keys: Vec<Cow<'static, str>>,
     action: &'static str,
 }
 
 impl KeyBind {
-    pub fn new(keys: Vec<&'static str>, action: &'static str) -> Self {
-        Self { keys, action }
+    pub fn new<K: Into<Cow<'static, str>>>(
+        keys: impl IntoIterator<Item = K>,
+        action: &'static str,
+    ) -> Self {
+        Self {
+            keys: keys.into_iter().map(Into::into).collect(),
+            action,
+        }
     }
     pub fn single_key(key: &'static str, action: &'static str) -> Self {
         Self {
-            keys: vec![key],
+            keys: vec![key.into()],
             action,
         }
     }
+
+    /// All of the keys, separated by slashes
+    pub fn keys(&self) -> String {
+        self.keys.join(" / ")
+    }
+
+    pub fn action(&self) -> &'static str {
+        self.action
+    }
 }
 
 pub struct FinishedKeybinds {
@@ -89,12 +106,12 @@ fn render_keybind(keybind: KeyBind) -> (Vec<Span<'static>>, u16) {
     spans.push(Span::raw("["));
     let (last, rest) = keybind.keys.split_last().unwrap();
     for k in rest {
-        length += k.len() + sep.len();
-        spans.push(Span::styled(*k, Style::default().bold()));
+        length += k.chars().count() + sep.len();
+        spans.push(Span::styled(k.clone(), Style::default().bold()));
         spans.push(Span::raw(sep));
     }
-    length += last.len();
-    spans.push(Span::styled(*last, Style::default().bold()));
+    length += last.chars().count();
+    spans.push(Span::styled(last.clone(), Style::default().bold()));
     spans.push(Span::raw("]: "));
 
     spans.push(Span::styled(keybind.action, Style::default().bold()));
@@ -0,0 +1,385 @@
+use std::{collections::BTreeMap, fmt, sync::LazyLock};
+
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{Deserialize, Serialize};
+
+use crate::{keybinds::KeyBind, settings::settings};
+
+static KEYMAP: LazyLock<Keymap> = LazyLock::new(|| {
+    Keymap::new(&settings().keybindings).expect("keybindings are validated on startup")
+});
+
+/// The active keymap, including any bindings from the configuration file
+pub fn keymap() -> &'static Keymap {
+    &KEYMAP
+}
+
+/// Actions which can be bound to keys
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Command {
+    Quit,
+    Back,
+    Select,
+    Next,
+    Prev,
+    Search,
+    ToggleFavourite,
+    ChangeArch,
+    LatestRelease,
+    ToggleSort,
+    ToggleBasket,
+    ViewBasket,
+    DownloadAll,
+    RemoveFromBasket,
+    ClearBasket,
+    StartOver,
+    PageDown,
+    PageUp,
+    First,
+    Last,
+    JumpToLetter,
+    GoToLine,
+    Help,
+    CopyUrl,
+    CopyAllUrls,
+    CopyUrlWithChecksum,
+    ViewLibrary,
+    VerifyImage,
+    DeleteImage,
+    UpdateImage,
+    JumpToPage,
+}
+
+impl Command {
+    const ALL: [Command; 31] = [
+        Command::Quit,
+        Command::Back,
+        Command::Select,
+        Command::Next,
+        Command::Prev,
+        Command::Search,
+        Command::ToggleFavourite,
+        Command::ChangeArch,
+        Command::LatestRelease,
+        Command::ToggleSort,
+        Command::ToggleBasket,
+        Command::ViewBasket,
+        Command::DownloadAll,
+        Command::RemoveFromBasket,
+        Command::ClearBasket,
+        Command::StartOver,
+        Command::PageDown,
+        Command::PageUp,
+        Command::First,
+        Command::Last,
+        Command::JumpToLetter,
+        Command::GoToLine,
+        Command::Help,
+        Command::CopyUrl,
+        Command::CopyAllUrls,
+        Command::CopyUrlWithChecksum,
+        Command::ViewLibrary,
+        Command::VerifyImage,
+        Command::DeleteImage,
+        Command::UpdateImage,
+        Command::JumpToPage,
+    ];
+
+    fn default_keys(&self) -> &'static [&'static str] {
+        match self {
+            Command::Quit => &["q"],
+            Command::Back => &["h"],
+            Command::Select => &["l", "Right", "Enter"],
+            Command::Next => &["j", "Down"],
+            Command::Prev => &["k", "Up"],
+            Command::Search => &["/"],
+            Command::ToggleFavourite => &["f"],
+            Command::ChangeArch => &["a"],
+            Command::LatestRelease => &["L"],
+            Command::ToggleSort => &["s"],
+            Command::ToggleBasket => &["Space"],
+            Command::ViewBasket => &["b"],
+            Command::DownloadAll => &["d"],
+            Command::RemoveFromBasket => &["x", "Delete"],
+            Command::ClearBasket => &["c"],
+            Command::StartOver => &["Ctrl+r"],
+            Command::PageDown => &["PageDown", "Ctrl+d"],
+            Command::PageUp => &["PageUp", "Ctrl+u"],
+            Command::First => &["Home"],
+            Command::Last => &["End", "G"],
+            Command::JumpToLetter => &["t"],
+            Command::GoToLine => &["g"],
+            Command::Help => &["?"],
+            Command::CopyUrl => &["y"],
+            Command::CopyAllUrls => &["Y"],
+            Command::CopyUrlWithChecksum => &["Ctrl+y"],
+            Command::ViewLibrary => &["i"],
+            Command::VerifyImage => &["v"],
+            Command::DeleteImage => &["D"],
+            Command::UpdateImage => &["u"],
+            Command::JumpToPage => &[
+                "Alt+1", "Alt+2", "Alt+3", "Alt+4", "Alt+5", "Alt+6", "Alt+7", "Alt+8", "Alt+9",
+            ],
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Command::Quit => "Exit",
+            Command::Back => "Previous page",
+            Command::Select => "Select current item",
+            Command::Next => "Select next item",
+            Command::Prev => "Select previous item",
+            Command::Search => "Enter Search",
+            Command::ToggleFavourite => "Toggle favourite",
+            Command::ChangeArch => "Change architecture",
+            Command::LatestRelease => "Open latest release",
+            Command::ToggleSort => "Reverse order",
+            Command::ToggleBasket => "Toggle in basket",
+            Command::ViewBasket => "View basket",
+            Command::DownloadAll => "Download all",
+            Command::RemoveFromBasket => "Remove from basket",
+            Command::ClearBasket => "Clear basket",
+            Command::StartOver => "Start over",
+            Command::PageDown => "Next page of items",
+            Command::PageUp => "Previous page of items",
+            Command::First => "First item",
+            Command::Last => "Last item",
+            Command::JumpToLetter => "Jump to item starting with a letter",
+            Command::GoToLine => "Go to line, when pressed twice",
+            Command::Help => "Help",
+            Command::CopyUrl => "Copy URL",
+            Command::CopyAllUrls => "Copy all URLs",
+            Command::CopyUrlWithChecksum => "Copy URL and checksum",
+            Command::ViewLibrary => "View downloaded images",
+            Command::VerifyImage => "Verify checksum",
+            Command::DeleteImage => "Delete image",
+            Command::UpdateImage => "Download latest release",
+            Command::JumpToPage => "Jump to page",
+        }
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Matches the names used in the configuration file
+        f.write_str(match self {
+            Command::Quit => "quit",
+            Command::Back => "back",
+            Command::Select => "select",
+            Command::Next => "next",
+            Command::Prev => "prev",
+            Command::Search => "search",
+            Command::ToggleFavourite => "toggle-favourite",
+            Command::ChangeArch => "change-arch",
+            Command::LatestRelease => "latest-release",
+            Command::ToggleSort => "toggle-sort",
+            Command::ToggleBasket => "toggle-basket",
+            Command::ViewBasket => "view-basket",
+            Command::DownloadAll => "download-all",
+            Command::RemoveFromBasket => "remove-from-basket",
+            Command::ClearBasket => "clear-basket",
+            Command::StartOver => "start-over",
+            Command::PageDown => "page-down",
+            Command::PageUp => "page-up",
+            Command::First => "first",
+            Command::Last => "last",
+            Command::JumpToLetter => "jump-to-letter",
+            Command::GoToLine => "go-to-line",
+            Command::Help => "help",
+            Command::CopyUrl => "copy-url",
+            Command::CopyAllUrls => "copy-all-urls",
+            Command::CopyUrlWithChecksum => "copy-url-with-checksum",
+            Command::ViewLibrary => "view-library",
+            Command::VerifyImage => "verify-image",
+            Command::DeleteImage => "delete-image",
+            Command::UpdateImage => "update-image",
+            Command::JumpToPage => "jump-to-page",
+        })
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum KeymapError {
+    #[error("Invalid key '{0}'")]
+    InvalidKey(String),
+    #[error("Key '{0}' is bound to both {1} and {2}")]
+    Conflict(String, Command, Command),
+    #[error("{0} must have at least one key")]
+    Unbound(Command),
+}
+
+/// A key, along with the modifiers which must be held
+#[derive(Clone, Copy, PartialEq)]
+pub struct KeySpec {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl KeySpec {
+    /// Parse a key such as `q`, `Enter`, `Space` or `Ctrl+d`
+    fn parse(spec: &str) -> Result<Self, KeymapError> {
+        let invalid = || KeymapError::InvalidKey(spec.to_string());
+        // A lone '+' is a key rather than a separator
+        let (modifiers, key) = match spec.rsplit_once('+') {
+            Some((modifiers, "")) => (modifiers.strip_suffix('+').unwrap_or(modifiers), "+"),
+            Some((modifiers, key)) => (modifiers, key),
+            None => ("", spec),
+        };
+        let modifiers = modifiers.split('+').filter(|m| !m.is_empty()).try_fold(
+            KeyModifiers::NONE,
+            |acc, m| {
+                Ok(acc
+                    | match m.to_lowercase().as_str() {
+                        "ctrl" | "control" => KeyModifiers::CONTROL,
+                        "alt" => KeyModifiers::ALT,
+                        "shift" => KeyModifiers::SHIFT,
+                        _ => return Err(invalid()),
+                    })
+            },
+        )?;
+
+        let mut chars = key.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match key.to_lowercase().as_str() {
+                "space" => KeyCode::Char(' '),
+                "enter" | "return" => KeyCode::Enter,
+                "esc" | "escape" => KeyCode::Esc,
+                "backspace" => KeyCode::Backspace,
+                "tab" => KeyCode::Tab,
+                "delete" | "del" => KeyCode::Delete,
+                "insert" => KeyCode::Insert,
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                f => match f.strip_prefix('f').and_then(|n| n.parse().ok()) {
+                    Some(n) => KeyCode::F(n),
+                    None => return Err(invalid()),
+                },
+            },
+        };
+        Ok(Self { code, modifiers })
+    }
+
+    fn matches(&self, key: &KeyEvent) -> bool {
+        match self.code {
+            // The case of a character already reflects whether shift was held
+            KeyCode::Char(_) => {
+                self.code == key.code
+                    && self.modifiers.difference(KeyModifiers::SHIFT)
+                        == key.modifiers.difference(KeyModifiers::SHIFT)
+            }
+            _ => self.code == key.code && self.modifiers == key.modifiers,
+        }
+    }
+}
+
+impl fmt::Display for KeySpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "Ctrl+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "Alt+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) && !matches!(self.code, KeyCode::Char(_)) {
+            write!(f, "Shift+")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) => write!(f, "{c}"),
+            KeyCode::F(n) => write!(f, "F{n}"),
+            code => f.write_str(match code {
+                KeyCode::Enter => "Enter",
+                KeyCode::Esc => "Esc",
+                KeyCode::Backspace => "Backspace",
+                KeyCode::Tab => "Tab",
+                KeyCode::Delete => "Delete",
+                KeyCode::Insert => "Insert",
+                KeyCode::Up => "Up",
+                KeyCode::Down => "Down",
+                KeyCode::Left => "Left",
+                KeyCode::Right => "Right",
+                KeyCode::Home => "Home",
+                KeyCode::End => "End",
+                KeyCode::PageUp => "PageUp",
+                KeyCode::PageDown => "PageDown",
+                _ => "Unknown",
+            }),
+        }
+    }
+}
+
+pub struct Keymap {
+    bindings: BTreeMap<Command, Vec<KeySpec>>,
+}
+
+impl Keymap {
+    /// Build the keymap from the defaults, replacing the keys of any commands which are configured
+    pub fn new(overrides: &BTreeMap<Command, Vec<String>>) -> Result<Self, KeymapError> {
+        let bindings = Command::ALL
+            .into_iter()
+            .map(|command| {
+                let keys: Result<Vec<_>, _> = match overrides.get(&command) {
+                    Some(keys) if keys.is_empty() => return Err(KeymapError::Unbound(command)),
+                    Some(keys) => keys.iter().map(|k| KeySpec::parse(k)).collect(),
+                    None => command
+                        .default_keys()
+                        .iter()
+                        .map(|k| KeySpec::parse(k))
+                        .collect(),
+                };
+                Ok((command, keys?))
+            })
+            .collect::<Result<BTreeMap<_, _>, _>>()?;
+
+        let all_keys: Vec<(Command, &KeySpec)> = bindings
+            .iter()
+            .flat_map(|(command, keys)| keys.iter().map(move |k| (*command, k)))
+            .collect();
+        for (i, (command, key)) in all_keys.iter().enumerate() {
+            if let Some((other, _)) = all_keys[i + 1..].iter().find(|(_, k)| k == key) {
+                return Err(KeymapError::Conflict(key.to_string(), *command, *other));
+            }
+        }
+        Ok(Self { bindings })
+    }
+
+    /// The command bound to a key press, if any
+    pub fn command(&self, key: &KeyEvent) -> Option<Command> {
+        self.bindings
+            .iter()
+            .find(|(_, keys)| keys.iter().any(|k| k.matches(key)))
+            .map(|(command, _)| *command)
+    }
+
+    /// Which of a command's keys was pressed, counting from one, for commands such as jump-to-page
+    /// whose keys are numbered by their position
+    pub fn key_number(&self, command: Command, key: &KeyEvent) -> Option<usize> {
+        let position = self.bindings[&command]
+            .iter()
+            .position(|k| k.matches(key))?;
+        Some(position + 1)
+    }
+
+    /// The keybind shown to the user for a command, with its default description
+    pub fn keybind(&self, command: Command) -> KeyBind {
+        self.keybind_with(command, command.description())
+    }
+
+    pub fn keybind_with(&self, command: Command, action: &'static str) -> KeyBind {
+        KeyBind::new(self.keys(command), action)
+    }
+
+    /// The keys bound to a command, as shown to the user
+    pub fn keys(&self, command: Command) -> impl Iterator<Item = String> + '_ {
+        self.bindings[&command].iter().map(|k| k.to_string())
+    }
+}
@@ -0,0 +1,8 @@
+//! Catalogue filtering, downloading, checksum verification and network configuration, as used by the quickosdl interface
+
+pub mod backend;
+pub mod catalogue;
+pub mod checksum;
+pub mod downloader;
+pub mod filename;
+pub mod network;
@@ -0,0 +1,513 @@
+use std::{
+    borrow::Cow,
+    cmp::Ordering,
+    collections::HashSet,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use quickget_core::data_structures::{Config, OS};
+use quickosdl::{
+    catalogue::{self, OSInfo, Selection},
+    checksum::{algorithm_name, verify_file},
+};
+use ratatui::{
+    crossterm::event::{KeyEvent, MouseEvent},
+    layout::Rect,
+    style::{Style, Stylize},
+    text::{Line, Span},
+    widgets::ListItem,
+    Frame,
+};
+use serde::{Deserialize, Serialize};
+use size::Size;
+
+use crate::{
+    app::{Action, Page},
+    arch_selection::parse_arch,
+    download::DownloadPage,
+    events::{self, AppEvent},
+    keybinds::KeyBind,
+    keymap::{keymap, Command},
+    os_selection::loaded_os_list,
+    release_selection::{compare_releases, latest_release},
+    searchable_list::{Highlights, SearchableItem, SearchableList},
+    status::Status,
+    theme::theme,
+};
+
+/// Indent of the line below each image's name
+const INDENT: &str = "   ";
+
+static LIBRARY: LazyLock<Mutex<Vec<LibraryEntry>>> = LazyLock::new(|| Mutex::new(load()));
+
+/// A downloaded image, remembered between runs
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LibraryEntry {
+    pub path: PathBuf,
+    pub os: String,
+    pub pretty_name: String,
+    pub release: String,
+    pub edition: Option<String>,
+    pub arch: String,
+    pub checksum: Option<String>,
+    /// When the image was downloaded, in seconds since the Unix epoch
+    pub downloaded: u64,
+    /// Size and modification time when the file was last checked, to notice changes without hashing it again
+    pub size: u64,
+    pub modified: u64,
+    /// Whether the file matched its checksum when it was last checked, or `None` if it hasn't been checked
+    #[serde(default)]
+    pub verified: Option<bool>,
+}
+
+/// Remember a downloaded image, replacing any earlier entry for the same file.
+/// Files which were kept rather than downloaded haven't been hashed, so they're only verified if an earlier check
+/// of the unchanged file was
+pub fn record(selection: &Selection, checksum: Option<&str>, path: &Path, downloaded: bool) {
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let Some((size, modified)) = file_stamp(&path) else {
+        return;
+    };
+    let mut entry = LibraryEntry {
+        os: selection.os.name.clone(),
+        pretty_name: selection.os.pretty_name.clone(),
+        release: selection.config.release.clone(),
+        edition: selection.config.edition.clone(),
+        arch: selection.config.arch.to_string(),
+        checksum: checksum.map(String::from),
+        downloaded: now(),
+        size,
+        modified,
+        // Downloads are verified as they arrive, if the checksum's algorithm is known
+        verified: (downloaded && checksum.and_then(algorithm_name).is_some()).then_some(true),
+        path,
+    };
+    update_library(|library| {
+        let earlier = library.iter().find(|e| e.path == entry.path);
+        if let Some(earlier) = earlier.filter(|_| !downloaded) {
+            entry.downloaded = earlier.downloaded;
+            entry.verified = earlier.verified.filter(|_| {
+                earlier.checksum == entry.checksum
+                    && (earlier.size, earlier.modified) == (entry.size, entry.modified)
+            });
+        }
+        library.retain(|e| e.path != entry.path);
+        library.push(entry);
+    });
+}
+
+fn entries() -> Vec<LibraryEntry> {
+    LIBRARY.lock().unwrap().clone()
+}
+
+/// Modify the library and write it back to disk
+fn update_library(f: impl FnOnce(&mut Vec<LibraryEntry>)) {
+    let mut library = LIBRARY.lock().unwrap();
+    f(&mut library);
+    // The library is a convenience; failing to persist it shouldn't interrupt the user
+    _ = save(&library);
+}
+
+fn path() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|dir| dir.join("quickosdl").join("library.json"))
+}
+
+fn load() -> Vec<LibraryEntry> {
+    path()
+        .and_then(|path| fs::read(path).ok())
+        .and_then(|data| serde_json::from_slice(&data).ok())
+        .unwrap_or_default()
+}
+
+fn save(library: &[LibraryEntry]) -> io::Result<()> {
+    let Some(path) = path() else {
+        return Ok(());
+    };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, serde_json::to_vec_pretty(library)?)
+}
+
+/// The size and modification time of a file
+fn file_stamp(path: &Path) -> Option<(u64, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some((metadata.len(), modified.as_secs()))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Format seconds since the Unix epoch as a UTC date, e.g. 2025-01-31
+fn format_date(secs: u64) -> String {
+    // Howard Hinnant's algorithm for converting days to civil dates
+    let days = (secs / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{year}-{month:02}-{day:02}")
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ImageStatus {
+    /// Unchanged since it last matched its checksum
+    Verified,
+    /// Unchanged since it was downloaded, but there's no checksum to verify it against
+    Unverified,
+    /// Unchanged since it was recorded, but never checked against its checksum
+    Unchecked,
+    /// Changed since it was downloaded, or no longer matches its checksum
+    Modified,
+    Missing,
+}
+
+impl ImageStatus {
+    fn of(entry: &LibraryEntry) -> Self {
+        match file_stamp(&entry.path) {
+            None => ImageStatus::Missing,
+            Some(stamp) if stamp != (entry.size, entry.modified) => ImageStatus::Modified,
+            Some(_) if !can_verify(entry) => ImageStatus::Unverified,
+            Some(_) => match entry.verified {
+                Some(true) => ImageStatus::Verified,
+                Some(false) => ImageStatus::Modified,
+                None => ImageStatus::Unchecked,
+            },
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            ImageStatus::Verified => "verified",
+            ImageStatus::Unverified => "no checksum",
+            ImageStatus::Unchecked => "not verified",
+            ImageStatus::Modified => "modified",
+            ImageStatus::Missing => "missing",
+        }
+    }
+}
+
+fn can_verify(entry: &LibraryEntry) -> bool {
+    entry.checksum.as_deref().and_then(algorithm_name).is_some()
+}
+
+/// The newest release of the same edition in the catalogue, if it's newer than the image
+fn newer_release(entry: &LibraryEntry, os_list: &[OS]) -> Option<Selection> {
+    let arch = parse_arch(&entry.arch).ok()?;
+    let os = os_list.iter().find(|os| os.name == entry.os)?;
+    let configs: Vec<Config> = catalogue::for_arch(std::slice::from_ref(os), arch)
+        .pop()?
+        .releases
+        .into_iter()
+        .filter(|c| c.edition == entry.edition)
+        .collect();
+    let latest = latest_release(&configs)?;
+    if compare_releases(latest, &entry.release) != Ordering::Greater {
+        return None;
+    }
+    let config = configs.iter().find(|c| c.release == latest)?.clone();
+    Some(Selection::new(OSInfo::from(os), config))
+}
+
+struct LibraryItem {
+    entry: LibraryEntry,
+    status: ImageStatus,
+    verifying: bool,
+    /// The latest release, if the image has been superseded
+    newer: Option<Selection>,
+}
+
+impl SearchableItem for LibraryItem {
+    fn to_list_item(&self, _: usize, highlights: Highlights) -> ListItem<'_> {
+        let entry = &self.entry;
+        let mut title = vec![Span::raw(format!("[{}] ", entry.arch))];
+        title.extend(highlights.spans(0, &entry.pretty_name, 0));
+        title.push(Span::raw(" "));
+        title.extend(highlights.spans(1, &entry.release, 0));
+        if let Some(edition) = &entry.edition {
+            title.push(Span::raw(" "));
+            title.extend(highlights.spans(2, edition, 0));
+        }
+        let status = if self.verifying {
+            Span::raw("verifying")
+        } else {
+            match self.status {
+                ImageStatus::Modified | ImageStatus::Missing => {
+                    Span::styled(self.status.label(), theme().error)
+                }
+                _ => Span::raw(self.status.label()),
+            }
+        };
+        title.extend([Span::raw("  ("), status, Span::raw(")")]);
+        if let Some(newer) = &self.newer {
+            title.push(Span::styled(
+                format!("  superseded by {}", newer.config.release),
+                Style::new().bold(),
+            ));
+        }
+
+        let path = entry.path.to_string_lossy();
+        let mut details = vec![Span::raw(INDENT)];
+        details.extend(
+            highlights
+                .spans(3, &path, 0)
+                .into_iter()
+                .map(|span| Span::styled(span.content.into_owned(), span.style)),
+        );
+        details.push(Span::styled(
+            format!(
+                "  {}, downloaded {}",
+                Size::from_bytes(entry.size),
+                format_date(entry.downloaded)
+            ),
+            Style::new().italic(),
+        ));
+        ListItem::new(vec![Line::from(title), Line::from(details)])
+    }
+    fn filter_fields(&self) -> Vec<Cow<'_, str>> {
+        let entry = &self.entry;
+        vec![
+            Cow::Borrowed(entry.pretty_name.as_str()),
+            Cow::Borrowed(&entry.release),
+            Cow::Borrowed(entry.edition.as_deref().unwrap_or_default()),
+            entry.path.to_string_lossy(),
+        ]
+    }
+}
+
+/// The outcome of verifying a file in the background
+type Verification = (PathBuf, io::Result<bool>);
+
+pub struct LibraryPage {
+    list: SearchableList<LibraryItem>,
+    /// Whether superseded images have been found, which needs the catalogue to have loaded
+    checked_catalogue: bool,
+    /// Files being verified in the background
+    verifying: HashSet<PathBuf>,
+    /// Verifications which have finished, for `update` to record
+    verified: Arc<Mutex<Vec<Verification>>>,
+    /// An image which is deleted if the delete key is pressed again
+    pending_delete: Option<PathBuf>,
+    /// Result of the last action
+    status: Option<Status>,
+}
+
+impl LibraryPage {
+    pub fn new() -> Self {
+        let mut page = Self {
+            list: SearchableList::new(vec![]),
+            checked_catalogue: false,
+            verifying: HashSet::new(),
+            verified: Arc::default(),
+            pending_delete: None,
+            status: None,
+        };
+        page.list = SearchableList::new(page.items());
+        page
+    }
+
+    /// Rebuild the list from the library
+    fn refresh(&mut self) {
+        let items = self.items();
+        self.list
+            .replace_items(items, |item| item.entry.path.clone());
+    }
+
+    /// Images in the library, newest downloads first
+    fn items(&mut self) -> Vec<LibraryItem> {
+        let os_list = loaded_os_list();
+        self.checked_catalogue = os_list.is_some();
+        let mut entries = entries();
+        entries.sort_by_key(|e| std::cmp::Reverse(e.downloaded));
+        entries
+            .into_iter()
+            .map(|entry| LibraryItem {
+                status: ImageStatus::of(&entry),
+                verifying: self.verifying.contains(&entry.path),
+                newer: os_list.and_then(|list| newer_release(&entry, list)),
+                entry,
+            })
+            .collect()
+    }
+
+    pub fn handle_key(&mut self, key: &KeyEvent) -> Option<Action> {
+        if !self.list.accepts_commands() {
+            self.list.handle_key(key);
+            return None;
+        }
+        let command = keymap().command(key);
+        let pending_delete = self.pending_delete.take();
+        match command {
+            Some(Command::Quit) => Some(Action::Exit),
+            Some(Command::Back) => Some(Action::PrevPage),
+            Some(Command::VerifyImage) => {
+                self.verify();
+                None
+            }
+            Some(Command::DeleteImage) => {
+                let path = self.list.selected_item()?.entry.path.clone();
+                if pending_delete.as_ref() == Some(&path) {
+                    self.delete(&path);
+                } else {
+                    let key = keymap()
+                        .keys(Command::DeleteImage)
+                        .next()
+                        .unwrap_or_default();
+                    self.status = Some(Status::info(format!(
+                        "Press {key} again to delete {}",
+                        path.display()
+                    )));
+                    self.pending_delete = Some(path);
+                }
+                None
+            }
+            Some(Command::UpdateImage) => {
+                let item = self.list.selected_item()?;
+                match &item.newer {
+                    Some(newer) => Some(Action::NextPage(Page::Download(DownloadPage::new(vec![
+                        newer.clone(),
+                    ])))),
+                    None => {
+                        let message = if self.checked_catalogue {
+                            format!("{} is the latest release", item.entry.release)
+                        } else {
+                            "The catalogue hasn't loaded yet".to_string()
+                        };
+                        self.status = Some(Status::info(message));
+                        None
+                    }
+                }
+            }
+            _ => {
+                self.list.handle_key(key);
+                None
+            }
+        }
+    }
+
+    /// Check the selected file against its checksum in the background
+    fn verify(&mut self) {
+        let Some(item) = self.list.selected_item() else {
+            return;
+        };
+        let entry = item.entry.clone();
+        if item.status == ImageStatus::Missing {
+            self.status = Some(Status::error(format!(
+                "{} is missing",
+                entry.path.display()
+            )));
+            return;
+        }
+        if !can_verify(&entry) {
+            self.status = Some(Status::error(
+                "This image has no checksum to verify against",
+            ));
+            return;
+        }
+        if !self.verifying.insert(entry.path.clone()) {
+            return;
+        }
+        let verified = self.verified.clone();
+        std::thread::spawn(move || {
+            let checksum = entry.checksum.unwrap_or_default().to_lowercase();
+            let result = verify_file(&entry.path, &checksum);
+            verified.lock().unwrap().push((entry.path, result));
+            events::send(AppEvent::VerificationFinished);
+        });
+        self.status = Some(Status::info("Verifying checksum"));
+        self.refresh();
+    }
+
+    fn delete(&mut self, path: &Path) {
+        match fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                self.status = Some(Status::error(format!(
+                    "Could not delete {}: {e}",
+                    path.display()
+                )));
+                return;
+            }
+            _ => {}
+        }
+        update_library(|library| library.retain(|e| e.path != path));
+        self.status = Some(Status::info(format!("Deleted {}", path.display())));
+        self.refresh();
+    }
+
+    /// Record finished verifications, and find superseded images once the catalogue has loaded
+    pub fn update(&mut self) -> Option<Action> {
+        let verified: Vec<Verification> = self.verified.lock().unwrap().drain(..).collect();
+        for (path, result) in &verified {
+            self.verifying.remove(path);
+            let name = path.display();
+            self.status = Some(match result {
+                Ok(matches) => {
+                    let stamp = file_stamp(path);
+                    update_library(|library| {
+                        let entry = library.iter_mut().find(|e| &e.path == path);
+                        if let (Some(entry), Some((size, modified))) = (entry, stamp) {
+                            (entry.size, entry.modified, entry.verified) =
+                                (size, modified, Some(*matches));
+                        }
+                    });
+                    if *matches {
+                        Status::info(format!("{name} matches its checksum"))
+                    } else {
+                        Status::error(format!("{name} doesn't match its checksum"))
+                    }
+                }
+                Err(e) => Status::error(format!("Could not verify {name}: {e}")),
+            });
+        }
+        if !verified.is_empty() || (!self.checked_catalogue && loaded_os_list().is_some()) {
+            self.refresh();
+        }
+        None
+    }
+
+    pub fn accepts_commands(&self) -> bool {
+        self.list.accepts_commands()
+    }
+
+    pub fn handle_mouse(&mut self, mouse: &MouseEvent) -> Option<Action> {
+        self.list.handle_mouse(mouse);
+        None
+    }
+
+    pub fn draw(&mut self, frame: &mut Frame, area: Rect) {
+        let area = Status::draw(self.status.as_ref(), frame, area);
+        self.list.draw(frame, area);
+    }
+
+    pub fn keybinds(&self) -> Vec<KeyBind> {
+        let mut binds = self.list.keybinds(true);
+        if !self.list.is_searching() && self.list.selected_item().is_some() {
+            let keymap = keymap();
+            binds.extend([
+                keymap.keybind(Command::VerifyImage),
+                keymap.keybind(Command::DeleteImage),
+            ]);
+            if self.list.selected_item().is_some_and(|i| i.newer.is_some()) {
+                binds.push(keymap.keybind(Command::UpdateImage));
+            }
+        }
+        binds
+    }
+}
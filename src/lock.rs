@@ -0,0 +1,237 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use itertools::Itertools;
+use quickget_core::data_structures::{Config, Source, WebSource, OS};
+use quickosdl::{
+    catalogue::{self, OSInfo, Selection},
+    checksum::verify_file,
+    downloader::display_name,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    arch_selection::{host_arch, parse_arch},
+    os_selection::os_list,
+    settings::settings,
+};
+
+pub const DEFAULT_MANIFEST: &str = "quickosdl.lock";
+const MANIFEST_VERSION: u32 = 1;
+
+/// Images pinned to exact URLs, file names and checksums, so the same set can be downloaded anywhere
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Manifest {
+    version: u32,
+    #[serde(default, rename = "image")]
+    images: Vec<LockedImage>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct LockedImage {
+    os: String,
+    pretty_name: String,
+    release: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    edition: Option<String>,
+    arch: String,
+    #[serde(rename = "file")]
+    files: Vec<WebSource>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum LockError {
+    #[error("{0}")]
+    Catalogue(String),
+    #[error("Invalid image '{0}', expected os/release[/edition][@arch]")]
+    Spec(String),
+    #[error("{0}")]
+    Arch(String),
+    #[error("No architecture given for '{0}', and the host architecture is unsupported")]
+    NoArch(String),
+    #[error("No image matches '{0}'")]
+    NotFound(String),
+    #[error("'{0}' has no files which can be downloaded directly")]
+    NoWebSources(String),
+    #[error("'{0}' has several editions, choose one of: {1}")]
+    Ambiguous(String, String),
+    #[error("Could not access {0}: {1}")]
+    Io(PathBuf, io::Error),
+    #[error("Invalid manifest {0}: {1}")]
+    Toml(PathBuf, toml::de::Error),
+    #[error("Unsupported manifest version {0}, expected {MANIFEST_VERSION}")]
+    Version(u32),
+}
+
+/// A file from a manifest which needs to be downloaded
+pub struct OutOfDate {
+    pub path: PathBuf,
+    /// Whether the file exists, but doesn't match its checksum
+    pub mismatched: bool,
+}
+
+impl Manifest {
+    /// Resolve images given as `os/release[/edition][@arch]` against the catalogue
+    pub fn resolve(specs: &[String]) -> Result<Self, LockError> {
+        let os_list = os_list().map_err(|e| LockError::Catalogue(e.to_string()))?;
+        let images = specs
+            .iter()
+            .map(|spec| resolve_spec(os_list, spec))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            version: MANIFEST_VERSION,
+            images,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, LockError> {
+        let contents =
+            fs::read_to_string(path).map_err(|e| LockError::Io(path.to_path_buf(), e))?;
+        let manifest: Self =
+            toml::from_str(&contents).map_err(|e| LockError::Toml(path.to_path_buf(), e))?;
+        if manifest.version != MANIFEST_VERSION {
+            return Err(LockError::Version(manifest.version));
+        }
+        Ok(manifest)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), LockError> {
+        let contents = toml::to_string_pretty(self).expect("manifests are always serializable");
+        let contents =
+            format!("# Written by quickosdl lock, replay with quickosdl sync\n{contents}");
+        fs::write(path, contents).map_err(|e| LockError::Io(path.to_path_buf(), e))
+    }
+
+    pub fn file_count(&self) -> usize {
+        self.images.iter().map(|i| i.files.len()).sum()
+    }
+
+    /// Find files which are missing or don't match their checksums, returning them
+    /// along with selections containing only those files, ready to be downloaded
+    pub fn out_of_date(&self) -> Result<(Vec<OutOfDate>, Vec<Selection>), LockError> {
+        let mut out_of_date = vec![];
+        let mut selections = vec![];
+        for image in &self.images {
+            let selection = image.selection()?;
+            let mut files = vec![];
+            for file in &image.files {
+                let path = settings().output_path(&display_name(file), &selection);
+                let exists = path.exists();
+                if exists {
+                    let matches = match &file.checksum {
+                        Some(checksum) => verify_file(&path, checksum)
+                            .map_err(|e| LockError::Io(path.clone(), e))?,
+                        None => true,
+                    };
+                    if matches {
+                        continue;
+                    }
+                }
+                out_of_date.push(OutOfDate {
+                    path,
+                    mismatched: exists,
+                });
+                files.push(Source::Web(file.clone()));
+            }
+            if !files.is_empty() {
+                let config = Config {
+                    iso: files,
+                    img: vec![],
+                    ..selection.config
+                };
+                selections.push(Selection::new(selection.os, config));
+            }
+        }
+        Ok((out_of_date, selections))
+    }
+}
+
+impl LockedImage {
+    fn selection(&self) -> Result<Selection, LockError> {
+        let os = OSInfo {
+            name: self.os.clone(),
+            pretty_name: self.pretty_name.clone(),
+        };
+        let config = Config {
+            release: self.release.clone(),
+            edition: self.edition.clone(),
+            arch: parse_arch(&self.arch).map_err(LockError::Arch)?,
+            iso: self.files.iter().cloned().map(Source::Web).collect(),
+            ..Default::default()
+        };
+        Ok(Selection::new(os, config))
+    }
+}
+
+fn resolve_spec(os_list: &[OS], spec: &str) -> Result<LockedImage, LockError> {
+    let (path, arch) = match spec.rsplit_once('@') {
+        Some((path, arch)) => (path, Some(parse_arch(arch).map_err(LockError::Arch)?)),
+        None => (spec, None),
+    };
+    let arch = arch
+        .or_else(|| settings().default_arch().ok().flatten())
+        .or_else(host_arch)
+        .ok_or_else(|| LockError::NoArch(spec.to_string()))?;
+    let mut parts = path.split('/');
+    let (Some(os), Some(release), edition, None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(LockError::Spec(spec.to_string()));
+    };
+
+    let not_found = || LockError::NotFound(spec.to_string());
+    let os = os_list
+        .iter()
+        .find(|o| o.name == os || o.pretty_name.eq_ignore_ascii_case(os))
+        .ok_or_else(not_found)?;
+    let matches = |c: &&Config| {
+        c.release == release
+            && c.arch == arch
+            && (edition.is_none() || c.edition.as_deref() == edition)
+    };
+    // Only images which could be downloaded from the interface are locked
+    let downloadable = catalogue::for_arch(std::slice::from_ref(os), arch)
+        .pop()
+        .map(|os| os.releases)
+        .unwrap_or_default();
+    let configs: Vec<&Config> = downloadable.iter().filter(matches).collect();
+    let config = match configs.as_slice() {
+        [] if os.releases.iter().any(|c| matches(&c)) => {
+            return Err(LockError::NoWebSources(spec.to_string()))
+        }
+        [] => return Err(not_found()),
+        [config] => *config,
+        configs => {
+            let editions = configs
+                .iter()
+                .filter_map(|c| c.edition.as_deref())
+                .join(", ");
+            return Err(LockError::Ambiguous(spec.to_string(), editions));
+        }
+    };
+
+    let selection = Selection::new(OSInfo::from(os), config.clone());
+    let files: Vec<WebSource> = selection
+        .sources()
+        .map(|source| WebSource {
+            // Pin the name, which would otherwise depend on redirects when downloading
+            file_name: Some(display_name(&source)),
+            ..source
+        })
+        .collect();
+    if files.is_empty() {
+        return Err(LockError::NoWebSources(spec.to_string()));
+    }
+    Ok(LockedImage {
+        os: os.name.clone(),
+        pretty_name: os.pretty_name.clone(),
+        release: config.release.clone(),
+        edition: config.edition.clone(),
+        arch: arch.to_string(),
+        files,
+    })
+}
@@ -1,62 +1,284 @@
 use std::{
+    env,
     io::{self, Stdout},
+    path::Path,
     time::Duration,
 };
 
-use app::App;
+use app::{App, Page};
+use clap::Parser;
+use download::DownloadPage;
+use events::AppEvent;
+use lock::{LockError, Manifest};
+use quickosdl::{catalogue::Selection, downloader::OverwritePolicy, network::Network};
 use ratatui::{
-    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    crossterm::{
+        event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyEventKind},
+        execute,
+    },
     prelude::CrosstermBackend,
     Terminal,
 };
+use report::Report;
+use settings::{Settings, SettingsError};
+use summary::ExitCode;
 
 mod app;
 mod arch_selection;
+mod basket;
+mod cli;
+mod clipboard;
 mod complete;
 mod download;
 mod download_options;
 mod edition_selection;
 mod error_display;
+mod events;
+mod export;
+mod fuzzy;
+mod help;
 mod keybinds;
+mod keymap;
+mod library;
+mod lock;
 mod os_selection;
 mod release_selection;
+mod report;
 mod searchable_list;
+mod settings;
+mod state;
+mod status;
+mod summary;
+mod theme;
 mod url_list;
 
 fn main() -> io::Result<()> {
-    let mut app = App::new();
+    let args = cli::Args::parse();
+    let settings = exit_on_error(load_settings(args.config.as_deref(), args.overrides));
 
+    if args.json {
+        report::enable();
+    }
+    let mut sync = None;
+    match args.command {
+        Some(cli::Command::Config) if args.json => {
+            let json =
+                serde_json::to_string_pretty(&settings).expect("settings are always serializable");
+            println!("{json}");
+            return Ok(());
+        }
+        Some(cli::Command::Config) => {
+            let path = args.config.or_else(Settings::default_path);
+            if let Some(path) = path {
+                let status = if path.exists() { "" } else { " (not found)" };
+                println!("# Configuration file: {}{status}", path.display());
+            }
+            print!("{}", settings.to_toml());
+            return Ok(());
+        }
+        Some(cli::Command::Lock { images, manifest }) => {
+            settings::init(settings);
+            if let Err(e) = write_lock(&images, &manifest) {
+                eprintln!("{e}");
+                let code = match e {
+                    LockError::Catalogue(_) => ExitCode::CatalogueUnavailable as i32,
+                    _ => 1,
+                };
+                std::process::exit(code);
+            }
+            return Ok(());
+        }
+        Some(cli::Command::Sync { manifest }) => {
+            settings::init(settings);
+            let selections = exit_on_error(out_of_date(&manifest));
+            if report::enabled() {
+                download_headless(selections);
+            }
+            if selections.is_empty() {
+                finish(false);
+            }
+            sync = Some(selections);
+        }
+        None if args.json => exit_on_error(Err(
+            "--json is only supported by the config, lock and sync commands",
+        )),
+        None => settings::init(settings),
+    }
+
+    let arch = settings::settings().default_arch().ok().flatten();
+    let mut app = App::new(arch.or_else(arch_selection::host_arch));
+    if let Some(selections) = sync {
+        app.push_page(Page::Download(sync_page(selections)));
+    }
+
+    let mouse = settings::settings().mouse_enabled();
     let mut terminal = ratatui::try_init()?;
+    if mouse {
+        execute!(io::stdout(), EnableMouseCapture)?;
+    }
     app.run(&mut terminal)?;
-    ratatui::try_restore()
+    if mouse {
+        execute!(io::stdout(), DisableMouseCapture)?;
+    }
+    ratatui::try_restore()?;
+    // Dropping the pages records any downloads they stop
+    drop(app);
+    finish(true)
+}
+
+/// Summarise the downloads, then exit with a code describing how they went.
+/// `quit` is whether the user ended the session through the interface
+fn finish(quit: bool) -> ! {
+    let results = summary::results();
+    if report::enabled() {
+        report::emit(summary::report(&results));
+    } else {
+        summary::print(&results);
+    }
+    if let Some(path) = &settings::settings().report_file {
+        if let Err(e) = summary::write_report(path, &results) {
+            eprintln!("Could not write report to {}: {e}", path.display());
+        }
+    }
+    std::process::exit(summary::exit_code(&results, quit) as i32)
+}
+
+fn exit_on_error<T>(result: Result<T, impl std::fmt::Display>) -> T {
+    result.unwrap_or_else(|e| {
+        eprintln!("{e}");
+        std::process::exit(1);
+    })
+}
+
+fn write_lock(images: &[String], path: &Path) -> Result<(), LockError> {
+    let manifest = Manifest::resolve(images)?;
+    manifest.save(path)?;
+    if report::enabled() {
+        let summary = serde_json::json!({
+            "manifest": path,
+            "images": images.len(),
+            "files": manifest.file_count(),
+        });
+        println!("{summary}");
+        return Ok(());
+    }
+    println!(
+        "Locked {} image(s), {} file(s) in {}",
+        images.len(),
+        manifest.file_count(),
+        path.display()
+    );
+    Ok(())
 }
 
+/// Report which files in the manifest need downloading, returning selections of them
+fn out_of_date(path: &Path) -> Result<Vec<Selection>, LockError> {
+    let manifest = Manifest::load(path)?;
+    let (files, selections) = manifest.out_of_date()?;
+    if files.is_empty() && !report::enabled() {
+        println!("All {} file(s) are up to date", manifest.file_count());
+    }
+    for file in files {
+        let reason = if file.mismatched {
+            "checksum mismatch"
+        } else {
+            "missing"
+        };
+        if report::enabled() {
+            report::emit(Report::OutOfDate {
+                path: &file.path,
+                reason,
+            });
+        } else {
+            println!("{}: {reason}", file.path.display());
+        }
+    }
+    Ok(selections)
+}
+
+/// Download the files `sync` found out of date. Only files which are missing or don't match their checksums
+/// are downloaded, so replacing them is safe
+fn sync_page(selections: Vec<Selection>) -> DownloadPage {
+    DownloadPage::with_overwrite(selections, OverwritePolicy::Overwrite)
+}
+
+/// Download without the interface, reporting progress as JSON
+fn download_headless(selections: Vec<Selection>) -> ! {
+    // Created first so no events from the downloads are missed
+    let events = events::channel();
+    let mut page = sync_page(selections);
+    while !page.is_finished() {
+        _ = events.recv_timeout(MAX_REDRAW_INTERVAL);
+        // Without the interface, a failure doesn't stop the other downloads
+        page.record_outcomes();
+    }
+    drop(page);
+    finish(false)
+}
+
+fn load_settings(
+    path: Option<&Path>,
+    overrides: cli::Overrides,
+) -> Result<Settings, SettingsError> {
+    let mut settings = Settings::load(path)?;
+    settings.apply_overrides(overrides);
+    settings.network.apply_env();
+    settings.validate()?;
+    export_proxy(&settings.network);
+    Ok(settings)
+}
+
+/// quickget_core fetches its catalogue itself, but does use the standard proxy variables
+fn export_proxy(network: &Network) {
+    if let Some(proxy) = &network.proxy {
+        for var in ["HTTPS_PROXY", "HTTP_PROXY"] {
+            env::set_var(var, proxy);
+        }
+    }
+    if let Some(hosts) = &network.no_proxy {
+        env::set_var("NO_PROXY", hosts);
+    }
+}
+
+/// Longest time between redraws when nothing has changed
+const MAX_REDRAW_INTERVAL: Duration = Duration::from_secs(1);
+
 impl App {
     fn run(&mut self, terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> io::Result<()> {
+        let events = events::channel();
         std::thread::spawn(move || {
             os_selection::init_os_list();
         });
+        std::thread::spawn(move || {
+            while let Ok(event) = event::read() {
+                events::send(AppEvent::Terminal(event));
+            }
+        });
         loop {
             terminal.draw(|f| self.draw(f))?;
 
-            if !event::poll(Duration::from_millis(10))? {
-                // Send a null key to ensure consistent refreshes
-                // Cost appears to be negligible
-                if self.handle_key(&KeyEvent::new(KeyCode::Null, KeyModifiers::NONE)) {
-                    break;
+            let event = events.recv_timeout(MAX_REDRAW_INTERVAL).ok();
+            // Handle everything that arrived while drawing, so bursts only cause a single redraw
+            for event in event.into_iter().chain(events.try_iter()) {
+                if self.handle_event(event) {
+                    return Ok(());
                 }
-                continue;
             }
+            if self.update() {
+                return Ok(());
+            }
+        }
+    }
 
-            if let Event::Key(key) = event::read()? {
-                if key.kind != KeyEventKind::Press && key.kind != KeyEventKind::Release {
-                    continue;
-                }
-                if self.handle_key(&key) {
-                    break;
-                }
+    fn handle_event(&mut self, event: AppEvent) -> bool {
+        match event {
+            AppEvent::Terminal(Event::Key(key))
+                if key.kind == KeyEventKind::Press || key.kind == KeyEventKind::Release =>
+            {
+                self.handle_key(&key)
             }
+            AppEvent::Terminal(Event::Mouse(mouse)) => self.handle_mouse(&mouse),
+            _ => false,
         }
-        Ok(())
     }
 }
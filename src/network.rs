@@ -0,0 +1,356 @@
+use std::{
+    collections::BTreeMap,
+    env,
+    ffi::OsString,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue, LOCATION},
+    redirect, Certificate, Client, Identity, Method, NoProxy, Proxy, RequestBuilder, Response, Url,
+};
+use serde::{Deserialize, Serialize};
+
+/// Seconds to wait for a connection, unless configured
+const DEFAULT_CONNECT_TIMEOUT: u64 = 30;
+/// Seconds to wait for more data from a server, unless configured
+const DEFAULT_READ_TIMEOUT: u64 = 60;
+const DEFAULT_USER_AGENT: &str = concat!("quickosdl/", env!("CARGO_PKG_VERSION"));
+/// Most redirects followed by a request
+const MAX_REDIRECTS: usize = 10;
+
+/// Where the system's trusted certificates are kept on common Linux distributions, macOS and the BSDs
+const SYSTEM_CERTIFICATES: [&str; 5] = [
+    "/etc/ssl/certs/ca-certificates.crt",
+    "/etc/pki/tls/certs/ca-bundle.crt",
+    "/etc/ssl/ca-bundle.pem",
+    "/etc/pki/tls/cacert.pem",
+    "/etc/ssl/cert.pem",
+];
+
+/// How to connect to servers, for the catalogue, downloads and external programs
+#[derive(Default, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Network {
+    /// Proxy for all requests, e.g. `http://proxy:3128` or `socks5://proxy:1080`.
+    /// Without one, the `HTTPS_PROXY`, `HTTP_PROXY` and `ALL_PROXY` variables are used
+    pub proxy: Option<String>,
+    /// Comma-separated hosts and domains to connect to directly, rather than through the proxy
+    pub no_proxy: Option<String>,
+    /// PEM file of extra certificates to trust
+    pub ca_bundle: Option<PathBuf>,
+    /// Whether to also trust the certificates installed on the system
+    pub system_certificates: bool,
+    /// PEM file of a client certificate to authenticate with, which may also contain its key
+    pub client_certificate: Option<PathBuf>,
+    /// PEM file of the client certificate's private key
+    pub client_key: Option<PathBuf>,
+    /// Seconds to wait for a connection to a server. Defaults to 30
+    pub connect_timeout: Option<u64>,
+    /// Seconds to wait for more data before a stalled request fails. Defaults to 60
+    pub read_timeout: Option<u64>,
+    /// User-Agent sent with every request. Defaults to `quickosdl/<version>`
+    pub user_agent: Option<String>,
+    /// Extra headers, such as cookies, sent to each host and its subdomains
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+/// An HTTP client which sends the configured headers to each host
+#[derive(Clone)]
+pub struct HttpClient {
+    client: Client,
+    headers: Arc<Vec<(String, HeaderMap)>>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum NetworkError {
+    #[error("Invalid proxy '{0}': {1}")]
+    Proxy(String, reqwest::Error),
+    #[error("Could not read {0}: {1}")]
+    Io(PathBuf, io::Error),
+    #[error("Invalid certificate in {0}: {1}")]
+    Certificate(PathBuf, reqwest::Error),
+    #[error("No certificates found in {0}")]
+    NoCertificates(PathBuf),
+    #[error("Could not find the system's certificates")]
+    NoSystemCertificates,
+    #[error("client-key was given without client-certificate")]
+    KeyWithoutCertificate,
+    #[error("Invalid header '{0}' for {1}")]
+    Header(String, String),
+    #[error("Timeouts must be at least 1 second")]
+    Timeout,
+    #[error("Invalid user-agent '{0}'")]
+    UserAgent(String),
+    #[error("Could not create HTTP client: {0}")]
+    Client(reqwest::Error),
+    #[error("{0}")]
+    Request(#[from] reqwest::Error),
+    #[error("Too many redirects from {0}")]
+    TooManyRedirects(String),
+}
+
+impl Network {
+    /// Fill options which aren't set from the environment: `SSL_CERT_FILE` for the CA bundle,
+    /// and `QUICKOSDL_CLIENT_CERT` and `QUICKOSDL_CLIENT_KEY` for the client certificate
+    pub fn apply_env(&mut self) {
+        let var = |name| {
+            env::var_os(name)
+                .filter(|v| !v.is_empty())
+                .map(PathBuf::from)
+        };
+        self.ca_bundle = self.ca_bundle.take().or_else(|| var("SSL_CERT_FILE"));
+        self.client_certificate = self
+            .client_certificate
+            .take()
+            .or_else(|| var("QUICKOSDL_CLIENT_CERT"));
+        self.client_key = self
+            .client_key
+            .take()
+            .or_else(|| var("QUICKOSDL_CLIENT_KEY"));
+    }
+
+    /// Build an HTTP client using these settings
+    pub fn client(&self) -> Result<HttpClient, NetworkError> {
+        if self.connect_timeout == Some(0) || self.read_timeout == Some(0) {
+            return Err(NetworkError::Timeout);
+        }
+        let user_agent = self.user_agent();
+        let user_agent = HeaderValue::from_str(user_agent)
+            .map_err(|_| NetworkError::UserAgent(user_agent.into()))?;
+        let mut builder = Client::builder()
+            // Redirects are followed by `HttpClient::send`, so each host only gets its own headers
+            .redirect(redirect::Policy::none())
+            .user_agent(user_agent)
+            .connect_timeout(Duration::from_secs(self.connect_timeout()))
+            .read_timeout(Duration::from_secs(self.read_timeout()));
+        if let Some(proxy) = &self.proxy {
+            let no_proxy = match &self.no_proxy {
+                Some(hosts) => NoProxy::from_string(hosts),
+                None => NoProxy::from_env(),
+            };
+            let proxy = Proxy::all(proxy)
+                .map_err(|e| NetworkError::Proxy(proxy.clone(), e))?
+                .no_proxy(no_proxy);
+            builder = builder.proxy(proxy);
+        }
+        for path in self.ca_bundle.iter().chain(self.system_bundle()?.as_ref()) {
+            let pem = read(path)?;
+            let certificates = Certificate::from_pem_bundle(&pem)
+                .map_err(|e| NetworkError::Certificate(path.clone(), e))?;
+            if certificates.is_empty() {
+                return Err(NetworkError::NoCertificates(path.clone()));
+            }
+            for certificate in certificates {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+        if let Some(path) = &self.client_certificate {
+            let mut pem = read(path)?;
+            if let Some(key) = &self.client_key {
+                pem.push(b'\n');
+                pem.extend(read(key)?);
+            }
+            let identity =
+                Identity::from_pem(&pem).map_err(|e| NetworkError::Certificate(path.clone(), e))?;
+            builder = builder.identity(identity);
+        } else if self.client_key.is_some() {
+            return Err(NetworkError::KeyWithoutCertificate);
+        }
+        let headers = self
+            .headers
+            .iter()
+            .map(|(host, headers)| Ok((host.clone(), header_map(host, headers)?)))
+            .collect::<Result<_, NetworkError>>()?;
+        Ok(HttpClient {
+            client: builder.build().map_err(NetworkError::Client)?,
+            headers: Arc::new(headers),
+        })
+    }
+
+    fn connect_timeout(&self) -> u64 {
+        self.connect_timeout.unwrap_or(DEFAULT_CONNECT_TIMEOUT)
+    }
+
+    fn read_timeout(&self) -> u64 {
+        self.read_timeout.unwrap_or(DEFAULT_READ_TIMEOUT)
+    }
+
+    fn user_agent(&self) -> &str {
+        self.user_agent.as_deref().unwrap_or(DEFAULT_USER_AGENT)
+    }
+
+    /// The extra headers to send with a request to a URL
+    fn headers_for<'a>(&'a self, url: &str) -> impl Iterator<Item = (&'a String, &'a String)> {
+        let host = host(url);
+        self.headers
+            .iter()
+            .filter(move |(pattern, _)| host.as_deref().is_some_and(|h| host_matches(h, pattern)))
+            .flat_map(|(_, headers)| headers)
+    }
+
+    fn system_bundle(&self) -> Result<Option<PathBuf>, NetworkError> {
+        if !self.system_certificates {
+            return Ok(None);
+        }
+        SYSTEM_CERTIFICATES
+            .iter()
+            .map(PathBuf::from)
+            .find(|path| path.exists())
+            .map(Some)
+            .ok_or(NetworkError::NoSystemCertificates)
+    }
+
+    /// Arguments giving these settings to curl, for downloading a URL.
+    /// Its CA bundle replaces its usual certificates.
+    /// It sends headers to any host a redirect leads to, so they shouldn't be configured when it's used
+    pub fn curl_args(&self, url: &str) -> Vec<OsString> {
+        let mut args = vec![];
+        let mut push = |flag: &str, value: &OsString| {
+            args.push(flag.into());
+            args.push(value.clone());
+        };
+        push("--user-agent", &self.user_agent().into());
+        push(
+            "--connect-timeout",
+            &self.connect_timeout().to_string().into(),
+        );
+        // Transfers slower than a byte per second for the read timeout are stalled
+        push("--speed-limit", &"1".into());
+        push("--speed-time", &self.read_timeout().to_string().into());
+        for (name, value) in self.headers_for(url) {
+            push("--header", &format!("{name}: {value}").into());
+        }
+        if let Some(proxy) = &self.proxy {
+            push("--proxy", &proxy.into());
+        }
+        if let Some(hosts) = &self.no_proxy {
+            push("--noproxy", &hosts.into());
+        }
+        if let Some(path) = &self.ca_bundle {
+            push("--cacert", &path.into());
+        }
+        if let Some(path) = &self.client_certificate {
+            push("--cert", &path.into());
+        }
+        if let Some(path) = &self.client_key {
+            push("--key", &path.into());
+        }
+        args
+    }
+
+    /// Arguments giving these settings to aria2c, for downloading a URL.
+    /// aria2c only supports HTTP proxies, and its CA bundle replaces its usual certificates.
+    /// It sends headers to any host a redirect leads to, so they shouldn't be configured when it's used
+    pub fn aria2c_args(&self, url: &str) -> Vec<OsString> {
+        let option = |name: &str, value: &Path| {
+            let mut arg = OsString::from(format!("--{name}="));
+            arg.push(value);
+            arg
+        };
+        let mut args = vec![
+            format!("--user-agent={}", self.user_agent()).into(),
+            format!("--connect-timeout={}", self.connect_timeout()).into(),
+            format!("--timeout={}", self.read_timeout()).into(),
+        ];
+        for (name, value) in self.headers_for(url) {
+            args.push(format!("--header={name}: {value}").into());
+        }
+        if let Some(proxy) = &self.proxy {
+            args.push(format!("--all-proxy={proxy}").into());
+        }
+        if let Some(hosts) = &self.no_proxy {
+            args.push(format!("--no-proxy={hosts}").into());
+        }
+        if let Some(path) = &self.ca_bundle {
+            args.push(option("ca-certificate", path));
+        }
+        if let Some(path) = &self.client_certificate {
+            args.push(option("certificate", path));
+            // aria2c always needs the key given separately, even if it's in the same file
+            args.push(option(
+                "private-key",
+                self.client_key.as_deref().unwrap_or(path),
+            ));
+        }
+        args
+    }
+}
+
+impl HttpClient {
+    pub async fn get(&self, url: &str) -> Result<Response, NetworkError> {
+        self.send(Method::GET, url).await
+    }
+
+    pub async fn head(&self, url: &str) -> Result<Response, NetworkError> {
+        self.send(Method::HEAD, url).await
+    }
+
+    /// Send a request, following redirects with the headers configured for each host along the way,
+    /// so headers meant for one host never reach another it redirects to
+    pub async fn send(&self, method: Method, url: &str) -> Result<Response, NetworkError> {
+        let mut response = self.request(method.clone(), url).send().await?;
+        let mut redirects = 0;
+        loop {
+            let location = response
+                .headers()
+                .get(LOCATION)
+                .and_then(|location| location.to_str().ok())
+                .and_then(|location| response.url().join(location).ok())
+                .filter(|_| response.status().is_redirection());
+            let Some(next) = location else {
+                return Ok(response);
+            };
+            if redirects == MAX_REDIRECTS {
+                return Err(NetworkError::TooManyRedirects(url.to_string()));
+            }
+            redirects += 1;
+            response = self.request(method.clone(), next.as_str()).send().await?;
+        }
+    }
+
+    /// Start a request, with the headers configured for the URL's host
+    fn request(&self, method: Method, url: &str) -> RequestBuilder {
+        let host = host(url);
+        let mut request = self.client.request(method, url);
+        for (pattern, headers) in self.headers.iter() {
+            if host.as_deref().is_some_and(|h| host_matches(h, pattern)) {
+                request = request.headers(headers.clone());
+            }
+        }
+        request
+    }
+}
+
+fn host(url: &str) -> Option<String> {
+    Url::parse(url).ok()?.host_str().map(String::from)
+}
+
+/// Whether a host is the configured host or one of its subdomains
+fn host_matches(host: &str, pattern: &str) -> bool {
+    let pattern = pattern.to_lowercase();
+    host == pattern
+        || host
+            .strip_suffix(&pattern)
+            .is_some_and(|subdomain| subdomain.ends_with('.'))
+}
+
+fn header_map(host: &str, headers: &BTreeMap<String, String>) -> Result<HeaderMap, NetworkError> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let invalid = || NetworkError::Header(name.clone(), host.to_string());
+            let name = HeaderName::from_bytes(name.as_bytes()).map_err(|_| invalid())?;
+            let value = HeaderValue::from_str(value).map_err(|_| invalid())?;
+            Ok((name, value))
+        })
+        .collect()
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, NetworkError> {
+    fs::read(path).map_err(|e| NetworkError::Io(path.to_path_buf(), e))
+}
@@ -3,13 +3,12 @@ use std::{
     sync::{LazyLock, Mutex},
 };
 
-use quickget_core::{
-    data_structures::{Arch, Config, Disk, Source, OS},
-    ConfigSearch, ConfigSearchError,
-};
+use quickget_core::data_structures::{Arch, Config, OS};
+use quickosdl::catalogue::{self, CatalogueError, OSInfo};
 use ratatui::{
-    crossterm::event::{KeyCode, KeyEvent},
+    crossterm::event::{KeyEvent, MouseEvent},
     layout::Rect,
+    style::{Style, Stylize},
     text::{Line, Span},
     widgets::ListItem,
     Frame,
@@ -18,30 +17,135 @@ use tokio::runtime::Runtime;
 
 use crate::{
     app::{Action, Page},
+    basket::{self, BasketPage},
+    download_options::DownloadOptions,
     error_display::ErrorDisplay,
+    events::{self, AppEvent},
     keybinds::KeyBind,
-    release_selection::ReleaseSelection,
-    searchable_list::{SearchableItem, SearchableList},
+    keymap::{keymap, Command},
+    library::LibraryPage,
+    release_selection::{self, ReleaseSelection},
+    searchable_list::{Highlights, SearchableItem, SearchableList},
+    settings::{client, settings},
+    state::{update_state, with_state},
 };
 
 pub fn init_os_list() {
     _ = OS_LIST.as_ref();
+    events::send(AppEvent::CatalogueLoaded);
+}
+
+/// The catalogue, loading it if necessary
+pub fn os_list() -> Result<&'static [OS], &'static CatalogueError> {
+    OS_LIST.as_ref().map(Vec::as_slice)
+}
+
+/// The catalogue, if it has already loaded
+pub fn loaded_os_list() -> Option<&'static [OS]> {
+    if !*OS_LIST_POPULATED.lock().unwrap() {
+        return None;
+    }
+    OS_LIST.as_ref().ok().map(Vec::as_slice)
+}
+
+/// Whether the catalogue has finished loading, and couldn't be loaded
+pub fn catalogue_failed() -> bool {
+    *OS_LIST_POPULATED.lock().unwrap() && OS_LIST.is_err()
 }
 
 static OS_LIST_POPULATED: Mutex<bool> = Mutex::new(false);
-static OS_LIST: LazyLock<Result<Vec<OS>, ConfigSearchError>> = LazyLock::new(|| {
+static OS_LIST: LazyLock<Result<Vec<OS>, CatalogueError>> = LazyLock::new(|| {
     let rt = Runtime::new().unwrap();
-    rt.block_on(async {
-        let search = ConfigSearch::new_without_cache().await;
-        *OS_LIST_POPULATED.lock().unwrap() = true;
-        search.map(|s| s.into_os_list())
-    })
+    let list = rt.block_on(catalogue::load(settings().catalogue.as_deref(), client()));
+    *OS_LIST_POPULATED.lock().unwrap() = true;
+    list
 });
 
-impl SearchableItem for OS {
-    fn to_list_item(&self, width: usize) -> ListItem {
-        let mut lines = vec![Line::from(vec![Span::raw(&self.pretty_name)])];
-        if let Some(description) = &self.description {
+#[derive(Clone, Copy, PartialEq)]
+enum Section {
+    Favourites,
+    Recent,
+    All,
+}
+
+impl Section {
+    fn heading(&self) -> &'static str {
+        match self {
+            Section::Favourites => "Favourites",
+            Section::Recent => "Recent",
+            Section::All => "All",
+        }
+    }
+}
+
+enum Target {
+    OS,
+    Release(String),
+    Config(Config),
+}
+
+pub struct OSEntry {
+    os: OS,
+    target: Target,
+    section: Section,
+    show_heading: bool,
+    favourite: bool,
+}
+
+impl OSEntry {
+    fn key(&self) -> (Section, String, Option<String>, Option<Option<String>>) {
+        let (release, edition) = match &self.target {
+            Target::OS => (None, None),
+            Target::Release(r) => (Some(r.clone()), None),
+            Target::Config(c) => (Some(c.release.clone()), Some(c.edition.clone())),
+        };
+        (self.section, self.os.name.clone(), release, edition)
+    }
+
+    /// The page opened by selecting this entry
+    fn page(&self) -> Page {
+        let os = OSInfo::from(&self.os);
+        match &self.target {
+            Target::OS => {
+                Page::ReleaseSelection(ReleaseSelection::new(os, self.os.releases.to_vec()))
+            }
+            Target::Release(release) => {
+                release_selection::release_page(os, &self.os.releases, release)
+            }
+            Target::Config(config) => {
+                Page::DownloadOptions(DownloadOptions::new(os, config.clone()))
+            }
+        }
+    }
+}
+
+impl SearchableItem for OSEntry {
+    fn to_list_item(&self, width: usize, highlights: Highlights) -> ListItem<'_> {
+        let mut lines = vec![];
+        if self.show_heading && !highlights.is_filtered() {
+            lines.push(Line::styled(
+                format!("── {} ──", self.section.heading()),
+                Style::new().bold(),
+            ));
+        }
+        let mut title = vec![];
+        if self.favourite {
+            title.push(Span::raw("★ "));
+        }
+        title.extend(highlights.spans(0, &self.os.pretty_name, 0));
+        match &self.target {
+            Target::OS => {}
+            Target::Release(release) => title.push(Span::raw(format!(" {release}"))),
+            Target::Config(config) => {
+                title.push(Span::raw(format!(" {}", config.release)));
+                if let Some(edition) = &config.edition {
+                    title.push(Span::raw(format!(" ({edition})")));
+                }
+            }
+        }
+        lines.push(Line::from(title));
+
+        if let (Section::All, Some(description)) = (self.section, &self.os.description) {
             let sep = "   ";
             let mut i = 0;
             while i < description.len() {
@@ -52,74 +156,187 @@ impl SearchableItem for OS {
                 } else {
                     description.len() - i - 1
                 };
-                lines.push(Line::from(vec![
-                    Span::raw(sep),
-                    Span::raw(&description[i..i + next_index]),
-                ]));
+                let mut line = vec![Span::raw(sep)];
+                line.extend(highlights.spans(2, &description[i..i + next_index], i));
+                lines.push(Line::from(line));
                 i += next_index + 1;
             }
         }
 
         ListItem::new(lines)
     }
-    fn to_filter(&self) -> Cow<'_, str> {
-        Cow::Borrowed(&self.name)
+    fn filter_fields(&self) -> Vec<Cow<'_, str>> {
+        // Pinned entries duplicate those in the full list, so they're hidden while searching
+        if self.section != Section::All {
+            return vec![];
+        }
+        let mut fields = vec![
+            Cow::Borrowed(self.os.pretty_name.as_str()),
+            Cow::Borrowed(&self.os.name),
+        ];
+        fields.extend(self.os.description.as_deref().map(Cow::Borrowed));
+        fields
     }
 }
 
 pub struct OSSelection {
     arch: Arch,
-    list: Option<SearchableList<OS>>,
+    os_list: Vec<OS>,
+    list: Option<SearchableList<OSEntry>>,
 }
 
 impl OSSelection {
     pub fn new(arch: Arch) -> Self {
-        let list = None;
-        Self { arch, list }
+        Self {
+            arch,
+            os_list: vec![],
+            list: None,
+        }
+    }
+
+    pub fn arch(&self) -> Arch {
+        self.arch
     }
 
     pub fn handle_key(&mut self, key: &KeyEvent) -> Option<Action> {
         if let Some(list) = &mut self.list {
-            match key.code {
-                KeyCode::Char('q') if !list.is_searching() => Some(Action::Exit),
-                KeyCode::Char('h') if !list.is_searching() => Some(Action::PrevPage),
-                _ => list.handle_key(key).map(|os| {
-                    Action::NextPage(Page::ReleaseSelection(ReleaseSelection::new(
-                        os.releases.to_vec(),
+            match keymap().command(key).filter(|_| list.accepts_commands()) {
+                Some(Command::Quit) => Some(Action::Exit),
+                Some(Command::Back | Command::ChangeArch) => Some(Action::PrevPage),
+                Some(Command::ViewBasket) if basket::len() > 0 => {
+                    Some(Action::NextPage(Page::Basket(BasketPage::new())))
+                }
+                Some(Command::ViewLibrary) => {
+                    Some(Action::NextPage(Page::Library(LibraryPage::new())))
+                }
+                Some(Command::LatestRelease) => {
+                    let entry = list.selected_item()?;
+                    let release = release_selection::latest_release(&entry.os.releases)?;
+                    Some(Action::NextPage(release_selection::release_page(
+                        OSInfo::from(&entry.os),
+                        &entry.os.releases,
+                        release,
                     )))
-                }),
+                }
+                Some(Command::ToggleFavourite) => {
+                    self.toggle_favourite();
+                    None
+                }
+                _ => list
+                    .handle_key(key)
+                    .map(|entry| Action::NextPage(entry.page())),
             }
         } else {
-            if *OS_LIST_POPULATED.lock().unwrap() {
-                match OS_LIST.as_ref() {
-                    Ok(list) => {
-                        let list: Vec<OS> = list
-                            .iter()
-                            .cloned()
-                            .map(|mut os| {
-                                os.releases.retain(has_only_wanted_sources);
-                                os.releases.retain(|c| correct_arch(c, &self.arch));
-                                os
-                            })
-                            .filter(|os| !os.releases.is_empty())
-                            .collect();
-                        self.list = Some(SearchableList::new(list));
-                    }
-                    Err(e) => {
-                        return Some(Action::NextPage(Page::Error(ErrorDisplay::new(vec![
-                            e.to_string()
-                        ]))))
-                    }
+            match keymap().command(key) {
+                Some(Command::Back | Command::ChangeArch) => Some(Action::PrevPage),
+                Some(Command::Quit) => Some(Action::Exit),
+                Some(Command::ViewLibrary) => {
+                    Some(Action::NextPage(Page::Library(LibraryPage::new())))
                 }
-            }
-            match key.code {
-                KeyCode::Char('h') => Some(Action::PrevPage),
-                KeyCode::Char('q') => Some(Action::Exit),
                 _ => None,
             }
         }
     }
 
+    /// Build the list once the catalogue has loaded
+    pub fn update(&mut self) -> Option<Action> {
+        if self.list.is_some() || !*OS_LIST_POPULATED.lock().unwrap() {
+            return None;
+        }
+        match OS_LIST.as_ref() {
+            Ok(list) => {
+                self.os_list = catalogue::for_arch(list, self.arch);
+                self.list = Some(SearchableList::new(self.entries()));
+                None
+            }
+            Err(e) => Some(Action::NextPage(Page::Error(ErrorDisplay::new(vec![
+                e.to_string()
+            ])))),
+        }
+    }
+
+    fn toggle_favourite(&mut self) {
+        let Some(entry) = self.list.as_ref().and_then(|l| l.selected_item()) else {
+            return;
+        };
+        update_state(|state| match &entry.target {
+            Target::Release(release) => state.toggle_favourite_release(&entry.os.name, release),
+            _ => state.toggle_favourite_os(&entry.os.name),
+        });
+        let entries = self.entries();
+        if let Some(list) = &mut self.list {
+            list.replace_items(entries, OSEntry::key);
+        }
+    }
+
+    /// Build the list, with favourites and recent selections for this architecture pinned to the top
+    fn entries(&self) -> Vec<OSEntry> {
+        let arch = self.arch.to_string();
+        let find_os = |name: &str| self.os_list.iter().find(|os| os.name == name);
+        let mut entries: Vec<OSEntry> = with_state(|state| {
+            let favourite_os = state
+                .favourite_os
+                .iter()
+                .filter_map(|name| find_os(name))
+                .map(|os| (os, Target::OS));
+            let favourite_releases = state.favourite_releases.iter().filter_map(|f| {
+                let os = find_os(&f.os)?;
+                os.releases
+                    .iter()
+                    .any(|c| c.release == f.release)
+                    .then(|| (os, Target::Release(f.release.clone())))
+            });
+            let recent = state
+                .recent
+                .iter()
+                .filter(|r| r.arch == arch)
+                .filter_map(|r| {
+                    let os = find_os(&r.os)?;
+                    let config = os
+                        .releases
+                        .iter()
+                        .find(|c| c.release == r.release && c.edition == r.edition)?;
+                    Some((os, Target::Config(config.clone()), Section::Recent))
+                });
+
+            favourite_os
+                .chain(favourite_releases)
+                .map(|(os, target)| (os, target, Section::Favourites))
+                .chain(recent)
+                .chain(self.os_list.iter().map(|os| (os, Target::OS, Section::All)))
+                .map(|(os, target, section)| OSEntry {
+                    favourite: match &target {
+                        Target::Release(release) => state.is_favourite_release(&os.name, release),
+                        _ => state.is_favourite_os(&os.name),
+                    },
+                    os: os.clone(),
+                    target,
+                    section,
+                    show_heading: false,
+                })
+                .collect()
+        });
+
+        let has_pinned = entries.iter().any(|e| e.section != Section::All);
+        let mut prev_section = None;
+        for entry in entries.iter_mut() {
+            entry.show_heading = has_pinned && prev_section != Some(entry.section);
+            prev_section = Some(entry.section);
+        }
+        entries
+    }
+
+    pub fn accepts_commands(&self) -> bool {
+        self.list
+            .as_ref()
+            .is_none_or(|list| list.accepts_commands())
+    }
+
+    pub fn handle_mouse(&mut self, mouse: &MouseEvent) -> Option<Action> {
+        let entry = self.list.as_mut()?.handle_mouse(mouse)?;
+        Some(Action::NextPage(entry.page()))
+    }
+
     pub fn draw(&mut self, frame: &mut Frame, area: Rect) {
         if let Some(list) = &mut self.list {
             list.draw(frame, area);
@@ -130,24 +347,27 @@ impl OSSelection {
     }
     pub fn keybinds(&self) -> Vec<KeyBind> {
         match &self.list {
-            Some(list) => list.keybinds(true),
-            None => vec![
-                KeyBind::single_key("q", "Exit"),
-                KeyBind::single_key("h", "Previous page"),
-            ],
+            Some(list) => {
+                let mut binds = list.keybinds(true);
+                if !list.is_searching() {
+                    let keymap = keymap();
+                    binds.push(keymap.keybind(Command::ToggleFavourite));
+                    binds.push(keymap.keybind(Command::ChangeArch));
+                    binds.push(keymap.keybind(Command::LatestRelease));
+                    binds.push(keymap.keybind(Command::ViewLibrary));
+                    binds.extend(basket::keybinds(false));
+                }
+                binds
+            }
+            None => {
+                let keymap = keymap();
+                vec![
+                    keymap.keybind(Command::Quit),
+                    keymap.keybind(Command::Back),
+                    keymap.keybind(Command::ChangeArch),
+                    keymap.keybind(Command::ViewLibrary),
+                ]
+            }
         }
     }
 }
-
-fn correct_arch(config: &Config, arch: &Arch) -> bool {
-    &config.arch == arch
-}
-
-fn has_only_wanted_sources(config: &Config) -> bool {
-    (config.disk_images.is_none() || config.disk_images == Some(vec![Disk::default()]))
-        && config
-            .iso
-            .iter()
-            .chain(config.img.iter())
-            .all(|s| matches!(s, Source::Web(_)))
-}
@@ -1,80 +1,342 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, cmp::Ordering};
 
 use itertools::Itertools;
 use quickget_core::data_structures::Config;
+use quickosdl::catalogue::OSInfo;
 use ratatui::{
-    crossterm::event::{KeyCode, KeyEvent},
+    crossterm::event::{KeyEvent, MouseEvent},
     layout::Rect,
-    text::Span,
+    style::{Style, Stylize},
+    text::{Line, Span},
     widgets::ListItem,
     Frame,
 };
 
 use crate::{
     app::{Action, Page},
+    basket::{self, BasketPage},
     download_options::DownloadOptions,
     edition_selection::EditionSelection,
     keybinds::KeyBind,
-    searchable_list::{SearchableItem, SearchableList},
+    keymap::{keymap, Command},
+    searchable_list::{Highlights, SearchableItem, SearchableList},
+    state::{update_state, with_state},
 };
 
 impl SearchableItem for String {
-    fn to_list_item(&self, _: usize) -> ListItem<'_> {
-        ListItem::from(Span::raw(self))
+    fn to_list_item(&self, _: usize, highlights: Highlights) -> ListItem<'_> {
+        ListItem::from(Line::from(highlights.spans(0, self, 0)))
     }
-    fn to_filter(&self) -> Cow<'_, str> {
-        Cow::Borrowed(self)
+    fn filter_fields(&self) -> Vec<Cow<'_, str>> {
+        vec![Cow::Borrowed(self)]
+    }
+}
+
+/// Releases which are builds of in-development versions, listed first but never considered the latest
+const DEVELOPMENT_RELEASES: [&str; 8] = [
+    "daily", "nightly", "dev", "devel", "edge", "sid", "unstable", "testing",
+];
+/// Releases which always point to the newest version
+const ROLLING_RELEASES: [&str; 4] = ["rolling", "latest", "current", "stable"];
+/// Suffixes marking pre-release versions, which sort before the release itself
+const PRE_RELEASE_MARKERS: [&str; 5] = ["alpha", "beta", "pre", "preview", "rc"];
+
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum ReleaseKind {
+    // Ordered from oldest to newest
+    Codename(String),
+    Version(Vec<VersionPart>),
+    Rolling,
+    Development,
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum VersionPart {
+    // A pre-release marker sorts before the end of a version, which sorts before any further parts
+    PreRelease(String),
+    End,
+    Text(String),
+    Number(u64),
+}
+
+impl ReleaseKind {
+    fn parse(release: &str) -> Self {
+        let lower = release.to_lowercase();
+        let first_word = lower.split(['-', '_', ' ']).next().unwrap_or_default();
+        if DEVELOPMENT_RELEASES.contains(&first_word) {
+            return Self::Development;
+        }
+        if ROLLING_RELEASES.contains(&first_word) {
+            return Self::Rolling;
+        }
+        if !lower.chars().any(|c| c.is_ascii_digit()) {
+            return Self::Codename(lower);
+        }
+
+        let mut parts: Vec<VersionPart> = lower
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|s| !s.is_empty())
+            .flat_map(|s| {
+                // Split tokens such as "11rc2" into their numeric and textual parts
+                s.chars()
+                    .chunk_by(char::is_ascii_digit)
+                    .into_iter()
+                    .map(|(_, chunk)| chunk.collect::<String>())
+                    .collect::<Vec<_>>()
+            })
+            .map(|s| match s.parse() {
+                Ok(n) => VersionPart::Number(n),
+                Err(_) if PRE_RELEASE_MARKERS.contains(&s.as_str()) => VersionPart::PreRelease(s),
+                Err(_) => VersionPart::Text(s),
+            })
+            .collect();
+        parts.push(VersionPart::End);
+        Self::Version(parts)
+    }
+}
+
+/// Compare releases by version, where a greater release is newer
+pub fn compare_releases(a: &str, b: &str) -> Ordering {
+    ReleaseKind::parse(a)
+        .cmp(&ReleaseKind::parse(b))
+        .then_with(|| a.cmp(b))
+}
+
+/// Whether a release is a long-term support release, if the data allows this to be determined
+fn is_lts(os: &OSInfo, release: &str) -> bool {
+    if release.to_lowercase().contains("lts") {
+        return true;
+    }
+    // Ubuntu and its flavours don't mark LTS releases, but they are the April releases of even years
+    os.name.contains("ubuntu")
+        && release
+            .split_once('.')
+            .and_then(|(year, month)| Some((year.parse::<u32>().ok()?, month)))
+            .is_some_and(|(year, month)| year % 2 == 0 && month == "04")
+}
+
+/// The newest release which isn't a development build
+pub fn latest_release(configs: &[Config]) -> Option<&str> {
+    configs
+        .iter()
+        .map(|c| c.release.as_str())
+        .filter(|r| ReleaseKind::parse(r) != ReleaseKind::Development)
+        .max_by(|a, b| compare_releases(a, b))
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ReleaseGroup {
+    Lts,
+    Interim,
+}
+
+struct Release {
+    name: String,
+    favourite: bool,
+    in_basket: bool,
+    group: Option<ReleaseGroup>,
+    show_heading: bool,
+}
+
+impl SearchableItem for Release {
+    fn to_list_item(&self, _: usize, highlights: Highlights) -> ListItem<'_> {
+        let mut lines = vec![];
+        if let (true, false, Some(group)) =
+            (self.show_heading, highlights.is_filtered(), self.group)
+        {
+            let heading = match group {
+                ReleaseGroup::Lts => "LTS",
+                ReleaseGroup::Interim => "Interim",
+            };
+            lines.push(Line::styled(
+                format!("── {heading} ──"),
+                Style::new().bold(),
+            ));
+        }
+        let mut line = vec![];
+        if self.in_basket {
+            line.push(Span::raw("[+] "));
+        }
+        if self.favourite {
+            line.push(Span::raw("★ "));
+        }
+        line.extend(highlights.spans(0, &self.name, 0));
+        lines.push(Line::from(line));
+        ListItem::from(lines)
+    }
+    fn filter_fields(&self) -> Vec<Cow<'_, str>> {
+        vec![Cow::Borrowed(&self.name)]
     }
 }
 
 pub struct ReleaseSelection {
+    os: OSInfo,
     configs: Vec<Config>,
-    list: SearchableList<String>,
+    newest_first: bool,
+    list: SearchableList<Release>,
 }
 
 impl ReleaseSelection {
-    pub fn new(configs: Vec<Config>) -> Self {
-        let releases: Vec<String> = configs
-            .iter()
-            .map(|c| &c.release)
-            .unique()
-            .cloned()
-            .collect();
+    pub fn new(os: OSInfo, configs: Vec<Config>) -> Self {
+        let releases = build_releases(&os, &configs, true);
         Self {
+            os,
             configs,
+            newest_first: true,
             list: SearchableList::new(releases),
         }
     }
 
     pub fn handle_key(&mut self, key: &KeyEvent) -> Option<Action> {
-        match key.code {
-            KeyCode::Char('q') if !self.list.is_searching() => Some(Action::Exit),
-            KeyCode::Char('h') if !self.list.is_searching() => Some(Action::PrevPage),
-            _ => self.list.handle_key(key).map(|r| {
-                let remaining_configs: Vec<Config> = self
-                    .configs
-                    .iter()
-                    .filter(|c| &c.release == r)
-                    .cloned()
-                    .collect();
-                if remaining_configs.len() == 1 {
-                    Action::NextPage(Page::DownloadOptions(DownloadOptions::new(
-                        remaining_configs.into_iter().next().unwrap(),
-                    )))
-                } else {
-                    Action::NextPage(Page::EditionSelection(EditionSelection::new(
-                        remaining_configs,
-                    )))
+        match keymap()
+            .command(key)
+            .filter(|_| self.list.accepts_commands())
+        {
+            Some(Command::Quit) => Some(Action::Exit),
+            Some(Command::Back) => Some(Action::PrevPage),
+            Some(Command::ToggleFavourite) => {
+                if let Some(release) = self.list.selected_item_mut() {
+                    update_state(|state| {
+                        state.toggle_favourite_release(&self.os.name, &release.name)
+                    });
+                    release.favourite = !release.favourite;
+                }
+                None
+            }
+            Some(Command::ToggleBasket) => {
+                if let Some(release) = self.list.selected_item() {
+                    basket::toggle_all(&self.os, &self.release_configs(&release.name));
                 }
-            }),
+                None
+            }
+            Some(Command::ViewBasket) if basket::len() > 0 => {
+                Some(Action::NextPage(Page::Basket(BasketPage::new())))
+            }
+            Some(Command::ToggleSort) => {
+                self.newest_first = !self.newest_first;
+                let releases = build_releases(&self.os, &self.configs, self.newest_first);
+                self.list.replace_items(releases, |r| r.name.clone());
+                None
+            }
+            Some(Command::LatestRelease) => latest_release(&self.configs)
+                .map(|r| Action::NextPage(release_page(self.os.clone(), &self.configs, r))),
+            _ => self
+                .list
+                .handle_key(key)
+                .map(|r| Action::NextPage(release_page(self.os.clone(), &self.configs, &r.name))),
         }
     }
 
+    pub fn selection(&self) -> Vec<String> {
+        vec![self.os.pretty_name.clone()]
+    }
+
+    pub fn accepts_commands(&self) -> bool {
+        self.list.accepts_commands()
+    }
+
+    pub fn handle_mouse(&mut self, mouse: &MouseEvent) -> Option<Action> {
+        let release = self.list.handle_mouse(mouse)?;
+        Some(Action::NextPage(release_page(
+            self.os.clone(),
+            &self.configs,
+            &release.name,
+        )))
+    }
+
+    fn release_configs(&self, release: &str) -> Vec<&Config> {
+        self.configs
+            .iter()
+            .filter(|c| c.release == release)
+            .collect()
+    }
+
     pub fn draw(&mut self, frame: &mut Frame, area: Rect) {
+        let configs = &self.configs;
+        for release in self.list.items_mut() {
+            release.in_basket = configs
+                .iter()
+                .any(|c| c.release == release.name && basket::contains(&self.os, c));
+        }
         self.list.draw(frame, area);
     }
 
     pub fn keybinds(&self) -> Vec<KeyBind> {
-        self.list.keybinds(true)
+        let mut binds = self.list.keybinds(true);
+        if !self.list.is_searching() {
+            let keymap = keymap();
+            binds.push(keymap.keybind(Command::ToggleFavourite));
+            binds.push(keymap.keybind_with(
+                Command::ToggleSort,
+                if self.newest_first {
+                    "Show oldest first"
+                } else {
+                    "Show newest first"
+                },
+            ));
+            binds.push(keymap.keybind(Command::LatestRelease));
+            binds.extend(basket::keybinds(true));
+        }
+        binds
+    }
+}
+
+/// Sort releases by version, grouping LTS and interim releases where they can be told apart
+fn build_releases(os: &OSInfo, configs: &[Config], newest_first: bool) -> Vec<Release> {
+    let mut names: Vec<&str> = configs
+        .iter()
+        .map(|c| c.release.as_str())
+        .unique()
+        .collect();
+    names.sort_by(|a, b| compare_releases(a, b));
+    if newest_first {
+        names.reverse();
+    }
+
+    let lts: Vec<bool> = names.iter().map(|r| is_lts(os, r)).collect();
+    let grouped = lts.contains(&true) && lts.contains(&false);
+    let mut releases: Vec<Release> = with_state(|state| {
+        names
+            .iter()
+            .zip(lts)
+            .map(|(name, lts)| Release {
+                favourite: state.is_favourite_release(&os.name, name),
+                in_basket: false,
+                group: grouped.then_some(if lts {
+                    ReleaseGroup::Lts
+                } else {
+                    ReleaseGroup::Interim
+                }),
+                show_heading: false,
+                name: name.to_string(),
+            })
+            .collect()
+    });
+    if grouped {
+        // Stable sort, so the version order is kept within each group
+        releases.sort_by_key(|r| r.group != Some(ReleaseGroup::Lts));
+        let mut prev_group = None;
+        for release in releases.iter_mut() {
+            release.show_heading = prev_group != release.group;
+            prev_group = release.group;
+        }
+    }
+    releases
+}
+
+/// The page following the selection of a release; editions are skipped if there's only one
+pub fn release_page(os: OSInfo, configs: &[Config], release: &str) -> Page {
+    let remaining_configs: Vec<Config> = configs
+        .iter()
+        .filter(|c| c.release == release)
+        .cloned()
+        .collect();
+    if remaining_configs.len() == 1 {
+        Page::DownloadOptions(DownloadOptions::new(
+            os,
+            remaining_configs.into_iter().next().unwrap(),
+        ))
+    } else {
+        Page::EditionSelection(EditionSelection::new(os, remaining_configs))
     }
 }
@@ -0,0 +1,147 @@
+use std::{
+    io::{self, Write},
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+use quickosdl::downloader::{Event, FailureKind};
+use serde::Serialize;
+
+use crate::summary::DownloadResult;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+/// Minimum time between progress reports printed by each download
+const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Print reports to stdout from now on, for `--json`
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Machine-readable events, printed as newline-delimited JSON when enabled.
+/// Downloads are identified by their position in the list of downloads
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum Report<'a> {
+    /// A file from a manifest which will be downloaded
+    OutOfDate {
+        path: &'a Path,
+        reason: &'static str,
+    },
+    Started {
+        id: usize,
+        name: &'a str,
+        url: &'a str,
+        path: &'a Path,
+        total: Option<u64>,
+    },
+    Progress {
+        id: usize,
+        bytes: u64,
+        total: Option<u64>,
+        /// Bytes per second since the previous progress event
+        speed: u64,
+    },
+    Retry {
+        id: usize,
+        attempt: u32,
+        error: String,
+    },
+    Checksum {
+        id: usize,
+        algorithm: &'static str,
+        matches: bool,
+    },
+    Completed {
+        id: usize,
+        bytes: u64,
+        /// Whether an existing file was kept rather than downloaded
+        skipped: bool,
+    },
+    Failed {
+        id: usize,
+        kind: FailureKind,
+        error: String,
+    },
+    /// The final result of every download
+    Summary {
+        completed: usize,
+        failed: usize,
+        downloads: Vec<DownloadResult>,
+    },
+}
+
+pub fn emit(report: Report) {
+    if !enabled() {
+        return;
+    }
+    let line = serde_json::to_string(&report).expect("reports are always serializable");
+    // Locking stdout keeps lines from concurrent downloads whole
+    _ = writeln!(io::stdout().lock(), "{line}");
+}
+
+/// Reports the events of a single download
+pub struct DownloadReporter<'a> {
+    id: usize,
+    url: &'a str,
+    last_report: Instant,
+    last_report_bytes: u64,
+}
+
+impl<'a> DownloadReporter<'a> {
+    pub fn new(id: usize, url: &'a str) -> Self {
+        Self {
+            id,
+            url,
+            last_report: Instant::now(),
+            last_report_bytes: 0,
+        }
+    }
+
+    pub fn event(&mut self, event: &Event) {
+        if !enabled() {
+            return;
+        }
+        let id = self.id;
+        emit(match *event {
+            Event::Started { name, path, total } => Report::Started {
+                id,
+                name,
+                url: self.url,
+                path,
+                total,
+            },
+            Event::Progress { bytes, total } => {
+                let elapsed = self.last_report.elapsed();
+                if elapsed < REPORT_INTERVAL {
+                    return;
+                }
+                let speed =
+                    bytes.saturating_sub(self.last_report_bytes) as f64 / elapsed.as_secs_f64();
+                (self.last_report, self.last_report_bytes) = (Instant::now(), bytes);
+                Report::Progress {
+                    id,
+                    bytes,
+                    total,
+                    speed: speed as u64,
+                }
+            }
+            Event::Retry { attempt, error } => Report::Retry {
+                id,
+                attempt,
+                error: error.to_string(),
+            },
+            Event::Checksum { algorithm, matches } => Report::Checksum {
+                id,
+                algorithm,
+                matches,
+            },
+            Event::Completed { bytes, skipped } => Report::Completed { id, bytes, skipped },
+        });
+    }
+}
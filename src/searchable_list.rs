@@ -1,22 +1,68 @@
 use std::borrow::Cow;
 
 use ratatui::{
-    crossterm::event::{KeyCode, KeyEvent, KeyModifiers},
-    layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Style},
+    crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind},
+    layout::{Constraint, Direction, Layout, Position, Rect},
     text::Span,
     widgets::{Block, Borders, HighlightSpacing, List, ListItem, ListState, Paragraph},
     Frame,
 };
 
-use crate::keybinds::KeyBind;
+use crate::{
+    fuzzy,
+    keybinds::KeyBind,
+    keymap::{keymap, Command},
+    theme::theme,
+};
 
-const HL_STYLE: Style = Style::new().bg(Color::LightBlue).fg(Color::Yellow);
 const HL_SYMBOL: &str = ">> ";
 
 pub trait SearchableItem {
-    fn to_list_item(&self, width: usize) -> ListItem<'_>;
-    fn to_filter(&self) -> Cow<'_, str>;
+    fn to_list_item(&self, width: usize, highlights: Highlights) -> ListItem<'_>;
+    /// Text fields which can be searched, ordered from most to least important
+    fn filter_fields(&self) -> Vec<Cow<'_, str>>;
+}
+
+/// Byte offsets of characters matched by the current search, per filter field
+#[derive(Clone, Copy)]
+pub struct Highlights<'a>(&'a [Vec<usize>]);
+
+impl Highlights<'_> {
+    pub const NONE: Highlights<'static> = Highlights(&[]);
+
+    /// Whether a search is filtering the list
+    pub fn is_filtered(&self) -> bool {
+        !self.0.is_empty()
+    }
+
+    pub fn field(&self, index: usize) -> &[usize] {
+        self.0.get(index).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Split text into spans, styling characters matched in the given field.
+    /// `offset` is the byte offset of text within the field, for fields that are rendered in parts
+    pub fn spans<'t>(&self, field: usize, text: &'t str, offset: usize) -> Vec<Span<'t>> {
+        let matches = self.field(field);
+        if matches.is_empty() {
+            return vec![Span::raw(text)];
+        }
+        let mut spans = vec![];
+        let mut start = 0;
+        for (index, c) in text.char_indices() {
+            if matches.binary_search(&(index + offset)).is_ok() {
+                if start < index {
+                    spans.push(Span::raw(&text[start..index]));
+                }
+                let end = index + c.len_utf8();
+                spans.push(Span::styled(&text[index..end], theme().search_match));
+                start = end;
+            }
+        }
+        if start < text.len() {
+            spans.push(Span::raw(&text[start..]));
+        }
+        spans
+    }
 }
 
 pub struct SearchableList<T>
@@ -25,9 +71,17 @@ where
 {
     items: Vec<T>,
     curr_item_indices: Vec<usize>,
+    curr_highlights: Vec<Vec<Vec<usize>>>,
     selected: ListState,
     searching: bool,
     search_query: String,
+    /// Vim-style count and prefix key typed before a command, e.g. `5` in `5j`, or `g` in `gg`
+    count: Option<usize>,
+    prefix: Option<Command>,
+    // Layout from the last draw, used to find the target of mouse clicks
+    search_area: Rect,
+    list_area: Rect,
+    item_heights: Vec<u16>,
 }
 
 impl<T: SearchableItem> SearchableList<T> {
@@ -40,9 +94,15 @@ impl<T: SearchableItem> SearchableList<T> {
         Self {
             items,
             curr_item_indices: vec![],
+            curr_highlights: vec![],
             selected,
             searching: false,
             search_query: String::new(),
+            count: None,
+            prefix: None,
+            search_area: Rect::default(),
+            list_area: Rect::default(),
+            item_heights: vec![],
         }
     }
 
@@ -57,36 +117,43 @@ impl<T: SearchableItem> SearchableList<T> {
                 let search_text = format!("{}█", &self.search_query);
                 Span::raw(search_text)
             } else {
-                let search_text = if self.search_query.is_empty() {
-                    "Press / to search"
+                let search_text = if let Some(pending) = self.pending_keys() {
+                    pending
+                } else if self.search_query.is_empty() {
+                    let key = keymap().keys(Command::Search).next().unwrap_or_default();
+                    format!("Press {key} to search")
                 } else {
-                    self.search_query.as_str()
+                    self.search_query.clone()
                 };
-                Span::styled(search_text, Style::default().fg(Color::Gray))
+                Span::styled(search_text, theme().search_hint)
             };
             Paragraph::new(search_text).block(Block::default().borders(Borders::BOTTOM))
         };
         frame.render_widget(search_bar, chunks[0]);
+        self.search_area = chunks[0];
+        self.list_area = chunks[1];
 
         let list = {
             let item_width = chunks[1].width as usize - HL_SYMBOL.len();
-            let mut items: Vec<ListItem> = if self.search_query.is_empty() {
+            let mut items: Vec<ListItem> = if !self.is_filtered() {
                 self.items
                     .iter()
-                    .map(|i| i.to_list_item(item_width))
+                    .map(|i| i.to_list_item(item_width, Highlights::NONE))
                     .collect()
             } else {
                 self.curr_item_indices
                     .iter()
-                    .map(|&i| self.items[i].to_list_item(item_width))
+                    .zip(&self.curr_highlights)
+                    .map(|(&i, h)| self.items[i].to_list_item(item_width, Highlights(h)))
                     .collect()
             };
+            self.item_heights = items.iter().map(|i| i.height() as u16).collect();
             if items.is_empty() {
                 self.selected.select(None);
                 items.push(ListItem::new(Span::raw("Nothing to see here")));
             }
             List::new(items)
-                .highlight_style(HL_STYLE)
+                .highlight_style(theme().highlight)
                 .highlight_symbol(HL_SYMBOL)
                 .highlight_spacing(HighlightSpacing::Always)
         };
@@ -115,25 +182,102 @@ impl<T: SearchableItem> SearchableList<T> {
                 _ => {}
             }
         } else {
-            match key.code {
-                KeyCode::Char('/') => self.enter_search(),
-                KeyCode::Char('l') | KeyCode::Right | KeyCode::Enter => {
-                    return if self.search_query.is_empty() {
-                        self.selected.selected().map(|i| &self.items[i])
-                    } else {
-                        self.selected
-                            .selected()
-                            .map(|i| &self.items[self.curr_item_indices[i]])
+            let count = self.count.take();
+            if let Some(prefix) = self.prefix.take() {
+                match (prefix, key.code) {
+                    (Command::GoToLine, _) if keymap().command(key) == Some(Command::GoToLine) => {
+                        self.select_line(count.unwrap_or(1))
                     }
+                    (Command::JumpToLetter, KeyCode::Char(c)) => self.jump_to_letter(c),
+                    _ => {}
+                }
+                return None;
+            }
+            let repeat = count.unwrap_or(1);
+            match (keymap().command(key), key.code) {
+                (Some(Command::Search), _) => self.enter_search(),
+                (Some(Command::Select), _) => return self.selected_item(),
+                (Some(Command::Next), _) => self.move_selection(repeat as isize),
+                (Some(Command::Prev), _) => self.move_selection(-(repeat as isize)),
+                (Some(Command::PageDown), _) => {
+                    self.move_selection((repeat * self.page_len()) as isize)
+                }
+                (Some(Command::PageUp), _) => {
+                    self.move_selection(-((repeat * self.page_len()) as isize))
+                }
+                (Some(Command::First), _) => self.select_line(1),
+                // As in vim, a count selects that line rather than the last one
+                (Some(Command::Last), _) => self.select_line(count.unwrap_or(usize::MAX)),
+                (Some(Command::JumpToLetter), _) => self.prefix = Some(Command::JumpToLetter),
+                (Some(Command::GoToLine), _) => {
+                    self.count = count;
+                    self.prefix = Some(Command::GoToLine);
+                }
+                (_, KeyCode::Char(c @ '0'..='9')) if count.is_some() || c != '0' => {
+                    let digit = c as usize - '0' as usize;
+                    self.count = Some(count.unwrap_or(0).saturating_mul(10).saturating_add(digit));
                 }
-                KeyCode::Down | KeyCode::Char('j') => self.select_next(),
-                KeyCode::Up | KeyCode::Char('k') => self.select_prev(),
                 _ => {}
             }
         }
         None
     }
 
+    /// Count and prefix keys waiting for a command
+    fn pending_keys(&self) -> Option<String> {
+        if self.count.is_none() && self.prefix.is_none() {
+            return None;
+        }
+        let count = self.count.map(|c| c.to_string()).unwrap_or_default();
+        let prefix = self
+            .prefix
+            .and_then(|command| keymap().keys(command).next())
+            .unwrap_or_default();
+        Some(format!("{count}{prefix}"))
+    }
+
+    /// Clicking an item selects it, and clicking the selected item activates it like the select key.
+    /// Clicking the search bar starts a search, and the scroll wheel moves the selection
+    pub fn handle_mouse(&mut self, mouse: &MouseEvent) -> Option<&T> {
+        let position = Position::new(mouse.column, mouse.row);
+        match mouse.kind {
+            MouseEventKind::ScrollDown => self.select_next(),
+            MouseEventKind::ScrollUp => self.select_prev(),
+            MouseEventKind::Down(MouseButton::Left)
+                if self.search_area.contains(position) && !self.is_searching() =>
+            {
+                self.enter_search()
+            }
+            MouseEventKind::Down(MouseButton::Left) if self.list_area.contains(position) => {
+                let index = self.item_at(mouse.row - self.list_area.y)?;
+                self.searching = false;
+                if self.selected.selected() == Some(index) {
+                    return self.selected_item();
+                }
+                self.selected.select(Some(index));
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// Index of the visible item drawn at the given row of the list
+    fn item_at(&self, row: u16) -> Option<usize> {
+        let mut top = 0;
+        for (index, height) in self
+            .item_heights
+            .iter()
+            .enumerate()
+            .skip(self.selected.offset())
+        {
+            top += height;
+            if row < top {
+                return Some(index);
+            }
+        }
+        None
+    }
+
     pub fn keybinds(&self, has_prev: bool) -> Vec<KeyBind> {
         if self.is_searching() {
             vec![
@@ -142,37 +286,115 @@ impl<T: SearchableItem> SearchableList<T> {
                 KeyBind::single_key("Backspace", "Remove last character"),
             ]
         } else {
+            let keymap = keymap();
             let mut binds = vec![
-                KeyBind::single_key("/", "Enter Search"),
-                KeyBind::new(vec!["l", "Right", "Enter"], "Select current item"),
-                KeyBind::new(vec!["j", "Down"], "Select next item"),
-                KeyBind::new(vec!["k", "Up"], "Select previous item"),
+                keymap.keybind(Command::Search),
+                keymap.keybind(Command::Select),
+                keymap.keybind(Command::Next),
+                keymap.keybind(Command::Prev),
+                keymap.keybind(Command::PageDown),
+                keymap.keybind(Command::PageUp),
+                KeyBind::new(
+                    keymap
+                        .keys(Command::First)
+                        .chain(keymap.keys(Command::GoToLine).map(|k| format!("{k}{k}"))),
+                    Command::First.description(),
+                ),
+                keymap.keybind(Command::Last),
+                KeyBind::new(
+                    keymap
+                        .keys(Command::JumpToLetter)
+                        .map(|k| format!("{k}<letter>")),
+                    "Jump to letter",
+                ),
                 // This keybind isn't part of the list, but all pages using the widget implement it
-                KeyBind::new(vec!["q"], "Exit"),
+                keymap.keybind(Command::Quit),
             ];
             if has_prev {
-                binds.push(KeyBind::new(vec!["h"], "Previous page"));
+                binds.push(keymap.keybind(Command::Back));
             }
             binds
         }
     }
 
-    fn update_items(&mut self) {
-        if self.search_query.is_empty() {
-            self.curr_item_indices.clear();
+    pub fn items(&self) -> &[T] {
+        &self.items
+    }
+
+    pub fn items_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.items.iter_mut()
+    }
+
+    /// The item currently highlighted, if any
+    pub fn selected_item(&self) -> Option<&T> {
+        self.selected_index().map(|i| &self.items[i])
+    }
+
+    pub fn selected_item_mut(&mut self) -> Option<&mut T> {
+        self.selected_index().map(|i| &mut self.items[i])
+    }
+
+    /// Highlight the item at the given index of the unfiltered list
+    pub fn select(&mut self, index: usize) {
+        if index < self.items.len() && !self.is_filtered() {
+            self.selected.select(Some(index));
+        }
+    }
+
+    /// Replace the contents of the list, keeping the search and, where possible, the selected item.
+    /// Items are identified across the replacement using the provided key
+    pub fn replace_items<K: PartialEq>(&mut self, items: Vec<T>, key: impl Fn(&T) -> K) {
+        let selected_key = self.selected_item().map(&key);
+        self.items = items;
+        self.update_items();
+
+        let visible: Vec<usize> = if self.is_filtered() {
+            self.curr_item_indices.clone()
         } else {
-            let query = self.search_query.to_lowercase();
-            self.curr_item_indices = self
-                .items
-                .iter()
-                .enumerate()
-                .filter_map(|(index, item)| {
-                    (item.to_filter().to_lowercase().contains(&query)).then_some(index)
-                })
-                .collect();
+            (0..self.items.len()).collect()
+        };
+        let position = selected_key
+            .and_then(|k| visible.iter().position(|&i| key(&self.items[i]) == k))
+            .or_else(|| {
+                let prev = self.selected.selected()?;
+                Some(prev.min(visible.len().checked_sub(1)?))
+            });
+        if self.selected.selected().is_some() || !self.is_searching() {
+            self.selected.select(position);
         }
     }
 
+    fn selected_index(&self) -> Option<usize> {
+        let index = self.selected.selected()?;
+        if self.is_filtered() {
+            self.curr_item_indices.get(index).copied()
+        } else {
+            (index < self.items.len()).then_some(index)
+        }
+    }
+
+    fn update_items(&mut self) {
+        self.curr_item_indices.clear();
+        self.curr_highlights.clear();
+        if !self.is_filtered() {
+            return;
+        }
+        let mut matches: Vec<(usize, fuzzy::FuzzyMatch)> = self
+            .items
+            .iter()
+            .enumerate()
+            .filter_map(|(index, item)| {
+                fuzzy::match_fields(&self.search_query, &item.filter_fields()).map(|m| (index, m))
+            })
+            .collect();
+        // Stable sort, so items with equal scores keep their original order
+        matches.sort_by_key(|(_, m)| std::cmp::Reverse(m.score));
+        (self.curr_item_indices, self.curr_highlights) = matches
+            .into_iter()
+            .map(|(index, m)| (index, m.highlights))
+            .unzip();
+    }
+
     fn enter_search(&mut self) {
         self.searching = true;
         self.selected.select(None);
@@ -182,26 +404,93 @@ impl<T: SearchableItem> SearchableList<T> {
         self.searching = false;
         self.search_query.clear();
         self.curr_item_indices.clear();
+        self.curr_highlights.clear();
         self.selected.select(Some(0));
     }
 
+    fn is_filtered(&self) -> bool {
+        !self.search_query.trim().is_empty()
+    }
+
     pub fn is_searching(&self) -> bool {
         self.searching
     }
 
-    fn select_next(&mut self) {
-        if self
-            .selected
-            .selected()
-            .is_some_and(|i| i < self.items.len() - 1)
-        {
-            self.selected.select_next();
+    /// Whether keys can be used as commands by the page, rather than being typed into the search
+    /// or completing a key sequence such as `gg` or `5j`
+    pub fn accepts_commands(&self) -> bool {
+        !self.searching && self.prefix.is_none() && self.count.is_none()
+    }
+
+    /// Number of items currently shown, which is fewer than the total while filtered by a search
+    fn visible_len(&self) -> usize {
+        if self.is_filtered() {
+            self.curr_item_indices.len()
+        } else {
+            self.items.len()
+        }
+    }
+
+    /// Move the selection by the given number of items, stopping at either end of the list
+    fn move_selection(&mut self, delta: isize) {
+        let last = self.visible_len().saturating_sub(1);
+        if let Some(index) = self.selected.selected() {
+            self.selected
+                .select(Some(index.saturating_add_signed(delta).min(last)));
         }
     }
 
+    fn select_next(&mut self) {
+        self.move_selection(1);
+    }
+
     fn select_prev(&mut self) {
-        if self.selected.selected().is_some_and(|i| i > 0) {
-            self.selected.select_previous();
+        self.move_selection(-1);
+    }
+
+    /// Select the item at a 1-based position, or the closest one
+    fn select_line(&mut self, line: usize) {
+        if self.selected.selected().is_some() {
+            let last = self.visible_len().saturating_sub(1);
+            self.selected.select(Some(line.saturating_sub(1).min(last)));
+        }
+    }
+
+    /// Number of items which fit in the list, starting from the first one shown
+    fn page_len(&self) -> usize {
+        let mut height = 0;
+        let fitting = self
+            .item_heights
+            .iter()
+            .skip(self.selected.offset())
+            .take_while(|h| {
+                height += **h;
+                height <= self.list_area.height
+            })
+            .count();
+        fitting.max(1)
+    }
+
+    /// Select the next item whose main field starts with the letter, wrapping around to the start
+    fn jump_to_letter(&mut self, letter: char) {
+        let Some(current) = self.selected.selected() else {
+            return;
+        };
+        let letter = letter.to_lowercase().collect::<String>();
+        let visible: Vec<usize> = if self.is_filtered() {
+            self.curr_item_indices.clone()
+        } else {
+            (0..self.items.len()).collect()
+        };
+        let len = visible.len();
+        let found = (1..=len).map(|i| (current + i) % len).find(|&i| {
+            self.items[visible[i]]
+                .filter_fields()
+                .first()
+                .is_some_and(|f| f.to_lowercase().starts_with(&letter))
+        });
+        if let Some(index) = found {
+            self.selected.select(Some(index));
         }
     }
 }
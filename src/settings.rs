@@ -0,0 +1,308 @@
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{LazyLock, OnceLock},
+};
+
+use quickget_core::data_structures::Arch;
+use quickosdl::{
+    backend::BackendKind,
+    catalogue::{self, Selection},
+    downloader::OverwritePolicy,
+    filename,
+    network::{HttpClient, Network, NetworkError},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    arch_selection::parse_arch,
+    cli::Overrides,
+    clipboard::ClipboardMethod,
+    keymap::{Command, Keymap, KeymapError},
+    theme::{Theme, ThemeConfig, ThemeError},
+};
+
+static SETTINGS: OnceLock<Settings> = OnceLock::new();
+static CLIENT: LazyLock<HttpClient> = LazyLock::new(|| {
+    settings()
+        .network
+        .client()
+        .expect("network settings are validated on startup")
+});
+
+const TEMPLATE_FIELDS: [&str; 6] = ["os", "release", "edition", "arch", "filename", "ext"];
+
+/// User configuration, read from `$XDG_CONFIG_HOME/quickosdl/config.toml`
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Settings {
+    /// Architecture to list images for, rather than detecting the host's architecture
+    pub default_arch: Option<String>,
+    /// Directory downloaded images are saved to, rather than the working directory
+    pub output_dir: Option<PathBuf>,
+    /// Template for downloaded file names, e.g. `{os}-{release}-{edition}-{arch}.{ext}`
+    pub filename_template: Option<String>,
+    pub overwrite: OverwritePolicy,
+    /// Maximum number of simultaneous downloads
+    pub concurrency: Option<usize>,
+    /// Total download speed limit, e.g. `10MiB` per second
+    pub rate_limit: Option<String>,
+    /// How files are downloaded: `builtin`, or by running `aria2c` or `curl`
+    pub backend: BackendKind,
+    /// URL or path of a JSON catalogue to use instead of the quickget_cigo data
+    pub catalogue: Option<String>,
+    /// Keys for each command, replacing the default keys of the commands listed
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub keybindings: BTreeMap<Command, Vec<String>>,
+    /// Whether to capture the mouse for clicking and scrolling. Defaults to true
+    pub mouse: Option<bool>,
+    /// Name of a built-in theme, or one defined in `themes`
+    pub theme: Option<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub themes: BTreeMap<String, ThemeConfig>,
+    /// How text copied from the URL list is delivered, `osc52` or `file`
+    pub clipboard: ClipboardMethod,
+    /// File to write a JSON summary of each session's downloads to
+    pub report_file: Option<PathBuf>,
+    /// Proxy, certificates and client certificate used for all requests
+    pub network: Network,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum SettingsError {
+    #[error("Could not read {0}: {1}")]
+    Io(PathBuf, io::Error),
+    #[error("Invalid configuration in {0}: {1}")]
+    Toml(PathBuf, toml::de::Error),
+    #[error("Invalid default-arch: {0}")]
+    Arch(String),
+    #[error("Invalid rate-limit '{0}', expected a size such as 500KiB or 10MB")]
+    RateLimit(String),
+    #[error("rate-limit can't be used with the {0} backend, which downloads files itself")]
+    RateLimitBackend(&'static str),
+    #[error("Headers can't be used with the {0} backend, which would send them to any host a download redirects to")]
+    HeadersBackend(&'static str),
+    #[error("concurrency must be at least 1")]
+    Concurrency,
+    #[error("Invalid keybindings: {0}")]
+    Keymap(#[from] KeymapError),
+    #[error("Invalid theme: {0}")]
+    Theme(#[from] ThemeError),
+    #[error("Invalid network settings: {0}")]
+    Network(#[from] NetworkError),
+    #[error("Unknown field '{{{0}}}' in filename-template, expected one of: {fields}", fields = TEMPLATE_FIELDS.join(", "))]
+    Template(String),
+}
+
+/// The active settings, including any command line overrides
+pub fn settings() -> &'static Settings {
+    SETTINGS.get_or_init(Settings::default)
+}
+
+/// The HTTP client for catalogues, file sizes and the builtin backend
+pub fn client() -> &'static HttpClient {
+    &CLIENT
+}
+
+pub fn init(settings: Settings) {
+    _ = SETTINGS.set(settings);
+}
+
+impl Settings {
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("quickosdl").join("config.toml"))
+    }
+
+    /// Load settings from the given file, or the default location. A missing default file is not an error
+    pub fn load(path: Option<&Path>) -> Result<Self, SettingsError> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => match Self::default_path().filter(|p| p.exists()) {
+                Some(path) => path,
+                None => return Ok(Self::default()),
+            },
+        };
+        let contents = fs::read_to_string(&path).map_err(|e| SettingsError::Io(path.clone(), e))?;
+        toml::from_str(&contents).map_err(|e| SettingsError::Toml(path, e))
+    }
+
+    pub fn apply_overrides(&mut self, overrides: Overrides) {
+        if let Some(arch) = overrides.arch {
+            self.default_arch = Some(arch.to_string());
+        }
+        self.output_dir = overrides.output_dir.or(self.output_dir.take());
+        self.filename_template = overrides
+            .filename_template
+            .or(self.filename_template.take());
+        self.overwrite = overrides.overwrite.map_or(self.overwrite, Into::into);
+        self.concurrency = overrides.concurrency.or(self.concurrency);
+        self.rate_limit = overrides.rate_limit.or(self.rate_limit.take());
+        self.backend = overrides.backend.map_or(self.backend, Into::into);
+        self.catalogue = overrides.catalogue.or(self.catalogue.take());
+        self.theme = overrides.theme.or(self.theme.take());
+        self.clipboard = overrides.clipboard.unwrap_or(self.clipboard);
+        self.report_file = overrides.report_file.or(self.report_file.take());
+        let network = &mut self.network;
+        network.connect_timeout = overrides.connect_timeout.or(network.connect_timeout);
+        network.read_timeout = overrides.read_timeout.or(network.read_timeout);
+        network.user_agent = overrides.user_agent.or(network.user_agent.take());
+        for header in overrides.headers {
+            let headers = network.headers.entry(header.host).or_default();
+            headers.insert(header.name, header.value);
+        }
+        if overrides.no_mouse {
+            self.mouse = Some(false);
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), SettingsError> {
+        self.default_arch()?;
+        if self.rate_limit()?.is_some() {
+            match self.backend {
+                BackendKind::Builtin => {}
+                BackendKind::Aria2c => return Err(SettingsError::RateLimitBackend("aria2c")),
+                BackendKind::Curl => return Err(SettingsError::RateLimitBackend("curl")),
+            }
+        }
+        if !self.network.headers.is_empty() {
+            match self.backend {
+                BackendKind::Builtin => {}
+                BackendKind::Aria2c => return Err(SettingsError::HeadersBackend("aria2c")),
+                BackendKind::Curl => return Err(SettingsError::HeadersBackend("curl")),
+            }
+        }
+        if self.concurrency == Some(0) {
+            return Err(SettingsError::Concurrency);
+        }
+        Keymap::new(&self.keybindings)?;
+        Theme::from_settings(self)?;
+        self.network.client()?;
+        if let Some(template) = &self.filename_template {
+            template_fields(template)
+                .find(|f| !TEMPLATE_FIELDS.contains(f))
+                .map_or(Ok(()), |f| Err(SettingsError::Template(f.to_string())))?;
+        }
+        Ok(())
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string_pretty(self).expect("settings are always serializable")
+    }
+
+    /// Whether the catalogue is read from a local path, so its `file://` URLs can be trusted
+    pub fn local_catalogue(&self) -> bool {
+        self.catalogue
+            .as_deref()
+            .is_some_and(|catalogue| !catalogue::is_remote(catalogue))
+    }
+
+    pub fn mouse_enabled(&self) -> bool {
+        self.mouse.unwrap_or(true)
+    }
+
+    pub fn default_arch(&self) -> Result<Option<Arch>, SettingsError> {
+        self.default_arch
+            .as_deref()
+            .map(parse_arch)
+            .transpose()
+            .map_err(SettingsError::Arch)
+    }
+
+    /// The rate limit in bytes per second
+    pub fn rate_limit(&self) -> Result<Option<u64>, SettingsError> {
+        self.rate_limit
+            .as_deref()
+            .map(|limit| parse_size(limit).ok_or_else(|| SettingsError::RateLimit(limit.into())))
+            .transpose()
+    }
+
+    /// Name a downloaded file is saved under within the output directory, given the file name provided by the source
+    pub fn file_name(&self, file_name: &str, selection: &Selection) -> String {
+        match &self.filename_template {
+            Some(template) => fill_template(template, file_name, selection),
+            None => file_name.to_string(),
+        }
+    }
+
+    /// Where a downloaded file should be written, given the file name provided by the source
+    pub fn output_path(&self, file_name: &str, selection: &Selection) -> PathBuf {
+        let file_name = self.file_name(file_name, selection);
+        match &self.output_dir {
+            Some(dir) => dir.join(file_name),
+            None => PathBuf::from(file_name),
+        }
+    }
+}
+
+fn template_fields(template: &str) -> impl Iterator<Item = &str> {
+    template
+        .split('{')
+        .skip(1)
+        .filter_map(|s| s.split_once('}').map(|(field, _)| field))
+}
+
+/// Fill a template's fields, each made safe to use as part of a file name so catalogue values can't leave the output
+/// directory. Fields are substituted in one pass, so braces within values are kept as they are.
+/// The finished name is sanitised too, shortening it to a length filesystems accept while keeping its extension
+fn fill_template(template: &str, file_name: &str, selection: &Selection) -> String {
+    let ext = file_name
+        .rsplit_once('.')
+        .map(|(_, ext)| ext)
+        .unwrap_or_default();
+    let arch = selection.config.arch.to_string();
+    let value = |field: &str| {
+        let value = match field {
+            "os" => selection.os.name.as_str(),
+            "release" => &selection.config.release,
+            "edition" => selection.config.edition.as_deref().unwrap_or_default(),
+            "arch" => &arch,
+            "filename" => file_name,
+            "ext" => ext,
+            _ => return None,
+        };
+        Some(filename::sanitize(value).unwrap_or_default())
+    };
+
+    let mut name = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        name.push_str(&rest[..start]);
+        let field = rest[start + 1..]
+            .split_once('}')
+            .and_then(|(field, after)| Some((value(field)?, after)));
+        match field {
+            Some((value, after)) => {
+                name.push_str(&value);
+                rest = after;
+            }
+            None => {
+                name.push('{');
+                rest = &rest[start + 1..];
+            }
+        }
+    }
+    name.push_str(rest);
+    filename::sanitize(&name).unwrap_or_else(|| file_name.to_string())
+}
+
+/// Parse a size such as `500K`, `10MiB` or `1.5 GB` into bytes
+fn parse_size(size: &str) -> Option<u64> {
+    let size = size.trim();
+    let unit_start = size
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(size.len());
+    let (value, unit) = size.split_at(unit_start);
+    let value: f64 = value.parse().ok()?;
+    let multiplier: u64 = match unit.trim().to_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" => 1000,
+        "kib" => 1 << 10,
+        "m" | "mb" => 1000 * 1000,
+        "mib" => 1 << 20,
+        "g" | "gb" => 1000 * 1000 * 1000,
+        "gib" => 1 << 30,
+        _ => return None,
+    };
+    Some((value * multiplier as f64) as u64).filter(|&size| size > 0)
+}
@@ -0,0 +1,114 @@
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{LazyLock, Mutex},
+};
+
+use serde::{Deserialize, Serialize};
+
+const MAX_RECENT: usize = 8;
+
+/// Persistent state remembered between runs, such as favourites and recent selections
+#[derive(Default, Serialize, Deserialize)]
+pub struct State {
+    #[serde(default)]
+    pub favourite_os: Vec<String>,
+    #[serde(default)]
+    pub favourite_releases: Vec<FavouriteRelease>,
+    #[serde(default)]
+    pub recent: Vec<RecentSelection>,
+    #[serde(default)]
+    pub last_arch: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct FavouriteRelease {
+    pub os: String,
+    pub release: String,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecentSelection {
+    pub arch: String,
+    pub os: String,
+    pub release: String,
+    pub edition: Option<String>,
+}
+
+static STATE: LazyLock<Mutex<State>> = LazyLock::new(|| Mutex::new(State::load()));
+
+/// Read the current state
+pub fn with_state<R>(f: impl FnOnce(&State) -> R) -> R {
+    f(&STATE.lock().unwrap())
+}
+
+/// Modify the state and write it back to disk
+pub fn update_state(f: impl FnOnce(&mut State)) {
+    let mut state = STATE.lock().unwrap();
+    f(&mut state);
+    // State is a convenience; failing to persist it shouldn't interrupt the user
+    _ = state.save();
+}
+
+impl State {
+    fn path() -> Option<PathBuf> {
+        dirs::state_dir()
+            .or_else(dirs::data_local_dir)
+            .map(|dir| dir.join("quickosdl").join("state.json"))
+    }
+
+    fn load() -> Self {
+        Self::path()
+            .and_then(|path| fs::read(path).ok())
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self) -> std::io::Result<()> {
+        let Some(path) = Self::path() else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_vec_pretty(self)?)
+    }
+
+    pub fn is_favourite_os(&self, os: &str) -> bool {
+        self.favourite_os.iter().any(|f| f == os)
+    }
+
+    pub fn toggle_favourite_os(&mut self, os: &str) {
+        match self.favourite_os.iter().position(|f| f == os) {
+            Some(index) => _ = self.favourite_os.remove(index),
+            None => self.favourite_os.push(os.to_string()),
+        }
+    }
+
+    pub fn is_favourite_release(&self, os: &str, release: &str) -> bool {
+        self.favourite_releases
+            .iter()
+            .any(|f| f.os == os && f.release == release)
+    }
+
+    pub fn toggle_favourite_release(&mut self, os: &str, release: &str) {
+        match self
+            .favourite_releases
+            .iter()
+            .position(|f| f.os == os && f.release == release)
+        {
+            Some(index) => _ = self.favourite_releases.remove(index),
+            None => self.favourite_releases.push(FavouriteRelease {
+                os: os.to_string(),
+                release: release.to_string(),
+            }),
+        }
+    }
+
+    /// Move a selection to the front of the recent list, keeping it bounded
+    pub fn push_recent(&mut self, selection: RecentSelection) {
+        self.recent.retain(|r| r != &selection);
+        self.recent.insert(0, selection);
+        self.recent.truncate(MAX_RECENT);
+    }
+}
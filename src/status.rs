@@ -0,0 +1,38 @@
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    text::Line,
+    widgets::Paragraph,
+    Frame,
+};
+
+use crate::theme::theme;
+
+/// Outcome of the last action taken on a page, shown beneath its contents
+pub struct Status(Result<String, String>);
+
+impl Status {
+    pub fn info(message: impl Into<String>) -> Self {
+        Self(Ok(message.into()))
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self(Err(message.into()))
+    }
+
+    /// Draw the status on the last line of the area, if there is one, returning the rest of the area
+    pub fn draw(status: Option<&Status>, frame: &mut Frame, area: Rect) -> Rect {
+        let Some(status) = status else {
+            return area;
+        };
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(1)])
+            .split(area);
+        let line = match &status.0 {
+            Ok(message) => Line::raw(message.as_str()),
+            Err(message) => Line::styled(message.as_str(), theme().error),
+        };
+        frame.render_widget(Paragraph::new(line), chunks[1]);
+        chunks[0]
+    }
+}
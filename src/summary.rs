@@ -0,0 +1,126 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use quickosdl::downloader::FailureKind;
+use serde::Serialize;
+use size::Size;
+
+use crate::{os_selection::catalogue_failed, report::Report};
+
+/// Results of every download this session, in the order they finished
+static RESULTS: Mutex<Vec<DownloadResult>> = Mutex::new(Vec::new());
+
+#[derive(Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ResultStatus {
+    Completed,
+    /// An existing file was kept
+    Skipped,
+    Failed,
+    Cancelled,
+    /// Stopped by quitting or starting over before it finished
+    Aborted,
+}
+
+#[derive(Clone, Serialize)]
+pub struct DownloadResult {
+    /// Position in the list of downloads it was started with
+    pub id: usize,
+    pub name: String,
+    pub status: ResultStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+    pub bytes: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<FailureKind>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Process exit codes. Clap exits with 2 for invalid arguments, and other errors such as invalid configuration exit with 1
+#[derive(Clone, Copy, PartialEq)]
+pub enum ExitCode {
+    Success = 0,
+    UserAbort = 3,
+    NetworkFailure = 4,
+    ChecksumMismatch = 5,
+    IoError = 6,
+    CatalogueUnavailable = 7,
+}
+
+pub fn record(result: DownloadResult) {
+    RESULTS.lock().unwrap().push(result);
+}
+
+pub fn results() -> Vec<DownloadResult> {
+    RESULTS.lock().unwrap().clone()
+}
+
+/// The exit code describing the results. Failures take precedence over aborted downloads,
+/// with checksum mismatches first as they may mean an image has been tampered with.
+/// `quit` is whether the user ended the session, which is an abort if nothing was downloaded
+pub fn exit_code(results: &[DownloadResult], quit: bool) -> ExitCode {
+    let failed = |kind| results.iter().any(|r| r.kind == Some(kind));
+    if failed(FailureKind::Checksum) {
+        ExitCode::ChecksumMismatch
+    } else if failed(FailureKind::Io) {
+        ExitCode::IoError
+    } else if failed(FailureKind::Network) {
+        ExitCode::NetworkFailure
+    } else if results
+        .iter()
+        .any(|r| matches!(r.status, ResultStatus::Cancelled | ResultStatus::Aborted))
+    {
+        ExitCode::UserAbort
+    } else if catalogue_failed() {
+        ExitCode::CatalogueUnavailable
+    } else if quit
+        && !results
+            .iter()
+            .any(|r| matches!(r.status, ResultStatus::Completed | ResultStatus::Skipped))
+    {
+        ExitCode::UserAbort
+    } else {
+        ExitCode::Success
+    }
+}
+
+pub fn report(results: &[DownloadResult]) -> Report<'static> {
+    let count = |f: fn(&ResultStatus) -> bool| results.iter().filter(|r| f(&r.status)).count();
+    Report::Summary {
+        completed: count(|s| matches!(s, ResultStatus::Completed | ResultStatus::Skipped)),
+        failed: count(|s| *s == ResultStatus::Failed),
+        downloads: results.to_vec(),
+    }
+}
+
+/// A line for each download, for printing once the terminal has been restored
+pub fn print(results: &[DownloadResult]) {
+    for result in results {
+        let path = result.path.as_ref().map(|p| p.display().to_string());
+        let path = path.as_deref().unwrap_or(&result.name);
+        match result.status {
+            ResultStatus::Completed => {
+                println!("Downloaded {path} ({})", Size::from_bytes(result.bytes))
+            }
+            ResultStatus::Skipped => println!("Kept existing {path}"),
+            ResultStatus::Failed => println!(
+                "Failed to download {}: {}",
+                result.name,
+                result.error.as_deref().unwrap_or_default()
+            ),
+            ResultStatus::Cancelled => println!("Cancelled {}", result.name),
+            ResultStatus::Aborted => println!("Stopped {} before it finished", result.name),
+        }
+    }
+}
+
+/// Write the results to a file as JSON
+pub fn write_report(path: &Path, results: &[DownloadResult]) -> io::Result<()> {
+    let json =
+        serde_json::to_string_pretty(&report(results)).expect("reports are always serializable");
+    fs::write(path, json + "\n")
+}
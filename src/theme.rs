@@ -0,0 +1,215 @@
+use std::{collections::BTreeMap, sync::LazyLock};
+
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use serde::{Deserialize, Serialize};
+
+use crate::settings::{settings, Settings};
+
+static THEME: LazyLock<Theme> =
+    LazyLock::new(|| Theme::from_settings(settings()).expect("theme is validated on startup"));
+
+const BUILTIN_THEMES: [&str; 4] = ["dark", "light", "high-contrast", "no-color"];
+
+/// The active theme, as configured or chosen by `NO_COLOR`
+pub fn theme() -> &'static Theme {
+    &THEME
+}
+
+/// Parts of the interface which can be styled
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Role {
+    Highlight,
+    SearchMatch,
+    SearchHint,
+    Error,
+    InProgress,
+    InProgressLabel,
+    Complete,
+    CompleteLabel,
+    Failed,
+    FailedLabel,
+}
+
+/// A custom theme, which replaces the styles of the given roles in a built-in theme
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct ThemeConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base: Option<String>,
+    #[serde(flatten)]
+    pub styles: BTreeMap<Role, StyleConfig>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StyleConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fg: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bg: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub modifiers: Vec<String>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ThemeError {
+    #[error("Unknown theme '{0}', expected one of: {builtin} or a theme defined in [themes]", builtin = BUILTIN_THEMES.join(", "))]
+    Unknown(String),
+    #[error("Custom theme '{0}' has the same name as a built-in theme")]
+    Builtin(String),
+    #[error("Invalid colour '{0}'")]
+    Colour(String),
+    #[error("Invalid modifier '{0}', expected bold, dim, italic, underlined or reversed")]
+    Modifier(String),
+}
+
+#[derive(Clone)]
+pub struct Theme {
+    /// The selected list item
+    pub highlight: Style,
+    /// Characters matched by a search
+    pub search_match: Style,
+    /// The search bar when it isn't focused
+    pub search_hint: Style,
+    pub error: Style,
+    // Download gauges. The bar is drawn in the foreground colour, labels are drawn over it
+    pub in_progress: Style,
+    pub in_progress_label: Style,
+    pub complete: Style,
+    pub complete_label: Style,
+    pub failed: Style,
+    pub failed_label: Style,
+}
+
+impl Theme {
+    fn dark() -> Self {
+        Self {
+            highlight: Style::new().bg(Color::Blue).fg(Color::White).bold(),
+            search_match: Style::new().bold().underlined(),
+            search_hint: Style::new().fg(Color::Gray),
+            error: Style::new().fg(Color::Red).bold(),
+            in_progress: Style::new().fg(Color::Blue),
+            in_progress_label: Style::new().fg(Color::White),
+            complete: Style::new().fg(Color::Green),
+            complete_label: Style::new().fg(Color::Black),
+            failed: Style::new().fg(Color::Red),
+            failed_label: Style::new().fg(Color::Black),
+        }
+    }
+
+    fn light() -> Self {
+        Self {
+            highlight: Style::new().bg(Color::LightBlue).fg(Color::Black).bold(),
+            search_hint: Style::new().fg(Color::DarkGray),
+            in_progress_label: Style::new().fg(Color::Black),
+            ..Self::dark()
+        }
+    }
+
+    /// Labels are drawn on a solid background so they're readable over any part of the gauge
+    fn high_contrast() -> Self {
+        let label = Style::new().fg(Color::White).bg(Color::Black).bold();
+        Self {
+            highlight: Style::new().reversed().bold(),
+            search_match: Style::new().fg(Color::LightYellow).bold().underlined(),
+            search_hint: Style::new().italic(),
+            error: Style::new().fg(Color::LightRed).bold().underlined(),
+            in_progress: Style::new().fg(Color::LightCyan),
+            in_progress_label: label,
+            complete: Style::new().fg(Color::LightGreen),
+            complete_label: label,
+            failed: Style::new().fg(Color::LightRed),
+            failed_label: label,
+        }
+    }
+
+    /// Only text attributes, for terminals or users which don't want colour
+    fn no_color() -> Self {
+        Self {
+            highlight: Style::new().reversed().bold(),
+            search_match: Style::new().bold().underlined(),
+            search_hint: Style::new().italic(),
+            error: Style::new().bold(),
+            in_progress: Style::new(),
+            in_progress_label: Style::new().bold(),
+            complete: Style::new(),
+            complete_label: Style::new().bold(),
+            failed: Style::new(),
+            failed_label: Style::new().bold(),
+        }
+    }
+
+    fn builtin(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Self::dark()),
+            "light" => Some(Self::light()),
+            "high-contrast" => Some(Self::high_contrast()),
+            "no-color" => Some(Self::no_color()),
+            _ => None,
+        }
+    }
+
+    /// The configured theme. Without one, `NO_COLOR` selects the no-color theme, following <https://no-color.org>
+    pub fn from_settings(settings: &Settings) -> Result<Self, ThemeError> {
+        if let Some(name) = settings.themes.keys().find(|n| Self::builtin(n).is_some()) {
+            return Err(ThemeError::Builtin(name.clone()));
+        }
+        let name = match &settings.theme {
+            Some(name) => name.as_str(),
+            None if std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) => "no-color",
+            None => "dark",
+        };
+        if let Some(theme) = Self::builtin(name) {
+            return Ok(theme);
+        }
+        let config = settings
+            .themes
+            .get(name)
+            .ok_or_else(|| ThemeError::Unknown(name.to_string()))?;
+        let base = config.base.as_deref().unwrap_or("dark");
+        let mut theme = Self::builtin(base).ok_or_else(|| ThemeError::Unknown(base.to_string()))?;
+        for (role, style) in &config.styles {
+            *theme.style_mut(*role) = style.to_style()?;
+        }
+        Ok(theme)
+    }
+
+    fn style_mut(&mut self, role: Role) -> &mut Style {
+        match role {
+            Role::Highlight => &mut self.highlight,
+            Role::SearchMatch => &mut self.search_match,
+            Role::SearchHint => &mut self.search_hint,
+            Role::Error => &mut self.error,
+            Role::InProgress => &mut self.in_progress,
+            Role::InProgressLabel => &mut self.in_progress_label,
+            Role::Complete => &mut self.complete,
+            Role::CompleteLabel => &mut self.complete_label,
+            Role::Failed => &mut self.failed,
+            Role::FailedLabel => &mut self.failed_label,
+        }
+    }
+}
+
+impl StyleConfig {
+    fn to_style(&self) -> Result<Style, ThemeError> {
+        let parse_colour = |c: &str| c.parse::<Color>().map_err(|_| ThemeError::Colour(c.into()));
+        let mut style = Style::new();
+        if let Some(fg) = &self.fg {
+            style = style.fg(parse_colour(fg)?);
+        }
+        if let Some(bg) = &self.bg {
+            style = style.bg(parse_colour(bg)?);
+        }
+        for modifier in &self.modifiers {
+            style = style.add_modifier(match modifier.to_lowercase().as_str() {
+                "bold" => Modifier::BOLD,
+                "dim" => Modifier::DIM,
+                "italic" => Modifier::ITALIC,
+                "underlined" => Modifier::UNDERLINED,
+                "reversed" => Modifier::REVERSED,
+                _ => return Err(ThemeError::Modifier(modifier.clone())),
+            });
+        }
+        Ok(style)
+    }
+}
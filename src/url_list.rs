@@ -1,35 +1,211 @@
+use std::borrow::Cow;
+
+use itertools::Itertools;
+use quickget_core::data_structures::{Source, WebSource};
+use quickosdl::{catalogue::Selection, checksum::algorithm_name, downloader::display_name};
 use ratatui::{
-    crossterm::event::{KeyCode, KeyEvent},
+    crossterm::event::{KeyEvent, MouseEvent},
     layout::Rect,
+    style::{Style, Stylize},
+    text::{Line, Span},
+    widgets::ListItem,
     Frame,
 };
+use size::Size;
+
+use crate::{
+    app::Action,
+    basket,
+    clipboard::{self, Copied},
+    keybinds::KeyBind,
+    keymap::{keymap, Command},
+    searchable_list::{Highlights, SearchableItem, SearchableList},
+    settings::settings,
+    status::Status,
+};
+
+/// Indent of the lines below each entry's file name
+const INDENT: &str = "   ";
+
+struct UrlEntry {
+    source: WebSource,
+    /// Whether the source is an ISO or a disk image
+    kind: &'static str,
+    /// Where the file will be saved, following the output directory and filename template
+    path: String,
+}
+
+impl SearchableItem for UrlEntry {
+    fn to_list_item(&self, width: usize, highlights: Highlights) -> ListItem<'_> {
+        let size = match basket::remote_size(&self.source.url) {
+            Some(Some(size)) => Size::from_bytes(size).to_string(),
+            Some(None) => "unknown size".to_string(),
+            None => "fetching size".to_string(),
+        };
+        let mut title = vec![Span::raw(format!("[{}] ", self.kind))];
+        title.extend(highlights.spans(0, &self.path, 0));
+        title.push(Span::styled(format!("  ({size})"), Style::new().italic()));
+
+        let mut lines = vec![Line::from(title)];
+        lines.extend(wrap(&self.source.url, width, |text, offset| {
+            highlights.spans(1, text, offset)
+        }));
+        match &self.source.checksum {
+            Some(checksum) => {
+                let algorithm = algorithm_name(checksum).unwrap_or("Checksum");
+                let label = format!("{algorithm}: ");
+                let mut checksum_lines =
+                    wrap(checksum, width - label.len().min(width), |text, _| {
+                        vec![Span::raw(text)]
+                    });
+                if let Some(first) = checksum_lines.first_mut() {
+                    first.spans.insert(1, Span::raw(label));
+                }
+                lines.extend(checksum_lines);
+            }
+            None => lines.push(Line::styled(
+                format!("{INDENT}No checksum"),
+                Style::new().italic(),
+            )),
+        }
+        ListItem::new(lines)
+    }
+    fn filter_fields(&self) -> Vec<Cow<'_, str>> {
+        vec![
+            Cow::Borrowed(self.path.as_str()),
+            Cow::Borrowed(&self.source.url),
+        ]
+    }
+}
 
-use crate::{app::Action, keybinds::KeyBind, searchable_list::SearchableList};
+/// Split text into indented lines which fit within the width, without breaking it at spaces as URLs and checksums have none.
+/// `spans` renders each part of the text, given its byte offset
+fn wrap<'t>(
+    text: &'t str,
+    width: usize,
+    spans: impl Fn(&'t str, usize) -> Vec<Span<'t>>,
+) -> Vec<Line<'t>> {
+    let width = width.saturating_sub(INDENT.len()).max(1);
+    let starts = text
+        .char_indices()
+        .map(|(i, _)| i)
+        .step_by(width)
+        .chain([text.len()]);
+    starts
+        .tuple_windows()
+        .map(|(start, end)| {
+            let mut line = vec![Span::raw(INDENT)];
+            line.extend(spans(&text[start..end], start));
+            Line::from(line)
+        })
+        .collect()
+}
 
 pub struct UrlList {
-    list: SearchableList<String>,
+    list: SearchableList<UrlEntry>,
+    /// Result of the last copy
+    status: Option<Status>,
 }
 
 impl UrlList {
-    pub fn new(urls: Vec<String>) -> Self {
+    pub fn new(selection: Selection) -> Self {
+        let sources = [
+            ("ISO", &selection.config.iso),
+            ("IMG", &selection.config.img),
+        ];
+        let entries: Vec<UrlEntry> = sources
+            .into_iter()
+            .flat_map(|(kind, sources)| sources.iter().map(move |s| (kind, s)))
+            .filter_map(|(kind, source)| match source {
+                Source::Web(web) => Some((kind, web.clone())),
+                _ => None,
+            })
+            .map(|(kind, source)| UrlEntry {
+                path: settings()
+                    .output_path(&display_name(&source), &selection)
+                    .display()
+                    .to_string(),
+                source,
+                kind,
+            })
+            .collect();
+        basket::fetch_sizes(entries.iter().map(|e| e.source.url.clone()).collect());
         Self {
-            list: SearchableList::new(urls),
+            list: SearchableList::new(entries),
+            status: None,
         }
     }
     pub fn handle_key(&mut self, key: &KeyEvent) -> Option<Action> {
-        match key.code {
-            KeyCode::Char('q') if !self.list.is_searching() => Some(Action::Exit),
-            KeyCode::Char('h') if !self.list.is_searching() => Some(Action::PrevPage),
+        match keymap()
+            .command(key)
+            .filter(|_| self.list.accepts_commands())
+        {
+            Some(Command::Quit) => Some(Action::Exit),
+            Some(Command::Back) => Some(Action::PrevPage),
+            Some(Command::CopyUrl) => {
+                if let Some(entry) = self.list.selected_item() {
+                    self.copy("URL", entry.source.url.clone());
+                }
+                None
+            }
+            Some(Command::CopyAllUrls) => {
+                let urls = self
+                    .list
+                    .items()
+                    .iter()
+                    .map(|e| e.source.url.as_str())
+                    .join("\n");
+                self.copy("all URLs", urls);
+                None
+            }
+            Some(Command::CopyUrlWithChecksum) => {
+                if let Some(entry) = self.list.selected_item() {
+                    match &entry.source.checksum {
+                        Some(checksum) => {
+                            let text = format!("{} {checksum}", entry.source.url);
+                            self.copy("URL and checksum", text);
+                        }
+                        None => self.status = Some(Status::error("This URL has no checksum")),
+                    }
+                }
+                None
+            }
             _ => {
                 self.list.handle_key(key);
                 None
             }
         }
     }
+    pub fn accepts_commands(&self) -> bool {
+        self.list.accepts_commands()
+    }
+
+    fn copy(&mut self, what: &str, text: String) {
+        self.status = Some(match clipboard::copy(&text) {
+            Ok(Copied::Clipboard) => Status::info(format!("Copied {what} to the clipboard")),
+            Ok(Copied::File(path)) => Status::info(format!("Wrote {what} to {}", path.display())),
+            Err(e) => Status::error(format!("Could not copy {what}: {e}")),
+        });
+    }
+
+    pub fn handle_mouse(&mut self, mouse: &MouseEvent) -> Option<Action> {
+        self.list.handle_mouse(mouse);
+        None
+    }
     pub fn draw(&mut self, frame: &mut Frame, area: Rect) {
+        let area = Status::draw(self.status.as_ref(), frame, area);
         self.list.draw(frame, area);
     }
     pub fn keybinds(&self) -> Vec<KeyBind> {
-        self.list.keybinds(true)
+        let mut binds = self.list.keybinds(true);
+        if !self.list.is_searching() {
+            let keymap = keymap();
+            binds.extend([
+                keymap.keybind(Command::CopyUrl),
+                keymap.keybind(Command::CopyAllUrls),
+                keymap.keybind(Command::CopyUrlWithChecksum),
+            ]);
+        }
+        binds
     }
 }